@@ -1,4 +1,4 @@
-use std::sync::mpsc::channel;
+use std::sync::{atomic::{AtomicU32, Ordering}, mpsc::channel};
 
 use futures_util::{stream::{SplitSink, SplitStream}, task, SinkExt, StreamExt, TryStreamExt};
 use tokio::{net::TcpStream, sync::mpsc::Sender};
@@ -24,7 +24,7 @@ struct ConnectedWebsocket {
 struct WebsocketChannelSender(Sender<String>);
 
 #[derive(Event)]
-struct SendEvent(MoveDirection);
+struct SendEvent(Option<MoveDirection>);
 
 enum MoveDirection {
     Up,
@@ -34,16 +34,30 @@ enum MoveDirection {
 }
 
 impl MoveDirection {
+    /// 누른 방향의 `common::PlayerInput` 버튼 (`ClientMessage`)
+    /// 누른 방향 하나만 보내며, 키를 떼면 `release_message`로 멈춘다.
     fn to_string(&self) -> String {
-        match self {
-            MoveDirection::Up => "up".to_string(),
-            MoveDirection::Down => "down".to_string(),
-            MoveDirection::Left => "left".to_string(),
-            MoveDirection::Right => "right".to_string(),
-        }
+        let buttons = match self {
+            MoveDirection::Up => 1 << 0,
+            MoveDirection::Down => 1 << 1,
+            MoveDirection::Left => 1 << 2,
+            MoveDirection::Right => 1 << 3,
+        };
+        input_message(buttons)
     }
 }
 
+/// 서버에 등록된 `common::InputPacket` client event 형식 (`NetworkMessage::NAME`: "InputPacket"), 입력을 보낼 때마다 tick을 하나씩 올린다.
+fn input_message(buttons: u8) -> String {
+    static TICK: AtomicU32 = AtomicU32::new(0);
+    let tick = TICK.fetch_add(1, Ordering::Relaxed) + 1;
+    format!(r#"{{"name":"InputPacket","value":{{"tick":{},"inputs":[{{"buttons":{}}}]}}}}"#, tick, buttons)
+}
+
+fn release_message() -> String {
+    input_message(0)
+}
+
 #[derive(Component)]
 struct Ball;
 
@@ -193,8 +207,11 @@ fn keyboard_input_system(mut keyboard_events: EventReader<KeyboardInput>, mut se
             };
 
             if let Some(direction) = move_direction {
-                send_event.write(SendEvent(direction));    
+                send_event.write(SendEvent(Some(direction)));    
             }
+        } else if matches!(event.key_code, KeyCode::ArrowUp | KeyCode::ArrowDown | KeyCode::ArrowLeft | KeyCode::ArrowRight) {
+            // 서버는 다음 입력까지 같은 입력으로 움직이므로 키를 떼면 멈추도록 알려준다.
+            send_event.write(SendEvent(None));
         }
     }
 }
@@ -206,7 +223,7 @@ fn send_event_system(mut send_event: EventReader<SendEvent>, websocket_sender: R
     for event in send_event.read() {
         // event 발생 시 websocket을 통해서 server로 보내준다.
         // handle을 이용해줘야하는 듯? 
-        let directino_str = event.0.to_string();
+        let directino_str = event.0.as_ref().map_or_else(release_message, MoveDirection::to_string);
         let sender_clone = websocket_sender.0.clone();
         handle.0.spawn(async move {
             // msg 생성 필요
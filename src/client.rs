@@ -1,14 +1,23 @@
-use std::sync::mpsc::{channel, Receiver};
+use std::{collections::{HashMap, VecDeque}, sync::{mpsc::{channel, Receiver}, Arc}};
 
-use futures_util::{stream::{SplitSink, SplitStream}, task, SinkExt, StreamExt, TryStreamExt};
-use tokio::{net::TcpStream, sync::mpsc::Sender};
-use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
-use bevy::{color::palettes::css::RED, input::{keyboard::KeyboardInput, ButtonState}, prelude::*};
+use futures_util::{stream::{SplitSink, SplitStream}, task, SinkExt, StreamExt};
+use tokio::{io::{AsyncRead, AsyncWrite}, net::TcpStream, sync::mpsc::Sender};
+use tokio_rustls::rustls::{self, pki_types::{pem::PemObject, CertificateDer}};
+use tokio_tungstenite::{tungstenite::{self, client::IntoClientRequest, handshake::client::Request, http::header::AUTHORIZATION, Message}, Connector, MaybeTlsStream, WebSocketStream};
+use bevy::{color::palettes::css::{BLUE, GRAY, RED}, prelude::*};
 
-use crate::common::{self, ServerMessage};
+use crate::common::{self, compression::{self as ws_compression, CompressionStats, COMPRESSION_HEADER, DEFLATE, MAX_DECOMPRESSED_SIZE}, ChannelKind, Frame, InputPacket, MatchInfo, PlayerInput, PlayerJoined, SequenceFilter, ServerMessage, TransportKind, ARENA_WALLS, INPUT_REDUNDANCY, PLAYER_RADIUS, TICK_RATE, UNIX_SOCKET_PATH};
+use clock::{ClientTick, ServerTimeEstimate, TimeSyncPlugin};
+use event::{ClientEventAppExt, ServerEventAppExt, ServerEvents};
+use resource::{ReplicateResourceExt, ReplicatedResources};
 
-#[derive(Resource)]
-struct TokioRuntimeHandle(tokio::runtime::Handle);
+mod clock;
+mod event;
+mod resource;
+mod udp;
+
+const DEFAULT_WEBSOCKET_URL: &str = "ws://127.0.0.1:9003";
+const DEFAULT_UDP_ADDR: &str = "127.0.0.1:9004";
 
 // websocket 연결 시 클라이언트 엔티티 생성 시 필요한 컴포넌트
 #[derive(Component)]
@@ -21,58 +30,74 @@ struct ConnectedWebsocket {
     stream: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
 }
 
+/// 서버로 보낼 메시지와 채널, WebSocket은 채널과 상관없이 순서대로 보낸다.
 #[derive(Resource)]
-struct WebsocketChannelSender(Sender<String>);
+struct WebsocketChannelSender(Sender<(ChannelKind, String)>);
 
 /// stream으로 받은 데이터를 보내주는 역할
 #[derive(Resource)]
-struct WebsocketStreamReceiver(tokio::sync::mpsc::Receiver<Vec3>);
+struct WebsocketStreamReceiver(tokio::sync::mpsc::Receiver<ServerMessage>);
 unsafe impl Sync for WebsocketStreamReceiver{}
 
-#[derive(Event)]
-struct SendEvent(MoveDirection);
+/// 서버 엔티티(bits) - 클라이언트 엔티티 매핑
+#[derive(Resource, Default)]
+struct NetworkEntityMap(HashMap<u64, Entity>);
 
-enum MoveDirection {
-    Up,
-    Down,
-    Left,
-    Right,
-}
+/// `Welcome` 메시지로 받은 내 플레이어의 서버 엔티티
+#[derive(Resource, Default)]
+struct LocalPlayer(Option<u64>);
 
-impl MoveDirection {
-    fn to_string(&self) -> String {
-        match self {
-            MoveDirection::Up => "up".to_string(),
-            MoveDirection::Down => "down".to_string(),
-            MoveDirection::Left => "left".to_string(),
-            MoveDirection::Right => "right".to_string(),
-        }
-    }
+/// 서버에서 spawn 메시지를 받을 때마다 생성하는 공 엔티티의 mesh, material
+#[derive(Resource)]
+struct BallAssets {
+    mesh: Handle<Mesh>,
+    local_material: Handle<ColorMaterial>,
+    remote_material: Handle<ColorMaterial>,
 }
 
+/// 최근에 보낸 입력 (최신 입력이 앞)
+#[derive(Resource, Default)]
+struct InputHistory(VecDeque<PlayerInput>);
+
 #[derive(Component)]
 struct Ball;
 
-pub fn run_client() {
+/// 내 플레이어 엔티티 표시
+#[derive(Component)]
+struct LocalBall;
+
+pub fn run_client(transport: TransportKind, url: Option<String>, ca_path: Option<String>, token: Option<String>, compression: bool) {
     // -------- tokio runtime 생성
     let runtime = tokio::runtime::Runtime::new().unwrap();
-    // -------- websocket connect task 생성..
+    // -------- websocket(udp) connect task 생성..
     let (sender, receiver) = runtime.handle().block_on(async move {
-        connect_websocket().await
+        match transport {
+            TransportKind::WebSocket => connect_websocket(url.unwrap_or(DEFAULT_WEBSOCKET_URL.to_string()), ca_path, token, compression).await,
+            TransportKind::Udp => udp::connect_udp(url.unwrap_or(DEFAULT_UDP_ADDR.to_string()), token).await,
+            TransportKind::UnixSocket => connect_unix_websocket(token, compression).await,
+        }
     });
 
     // -------- bevy App initialize
     App::new()
         .add_plugins(DefaultPlugins)
-        .add_event::<SendEvent>()
-        .insert_resource(TokioRuntimeHandle(runtime.handle().clone()))
         .insert_resource(WebsocketChannelSender(sender))
         .insert_resource(WebsocketStreamReceiver(receiver))
+        .init_resource::<NetworkEntityMap>()
+        .init_resource::<LocalPlayer>()
+        .replicate_resource::<MatchInfo>()
+        .add_server_event::<PlayerJoined>()
+        // 입력은 최근 입력을 같이 보내므로 유실돼도 다시 보낼 필요가 없다.
+        .add_client_event_on::<InputPacket>(ChannelKind::UnreliableSequenced)
+        .init_resource::<InputHistory>()
+        .insert_resource(Time::<Fixed>::from_hz(TICK_RATE))
+        .add_plugins(TimeSyncPlugin)
         .add_systems(Startup, setup)
+        .add_systems(FixedUpdate, keyboard_input_system)
         .add_systems(Update, (
-            keyboard_input_system,
-            send_event_system,
-            move_sync_system,
+            server_message_system,
+            match_info_system,
+            player_joined_system,
             )
         )
         .run();
@@ -81,18 +106,94 @@ pub fn run_client() {
 // region: --websocket
 /// 어플리케이션 실행 시 WebSocket 연결 함수
 /// stream, sink를 처리하는 task를 각각 생성한다.
-/// Sender<String>을 반환하여 Bevy의 resource로 만들어 Bevy App에서 사용하도록 하였음. 
+/// Sender<(ChannelKind, String)>을 반환하여 Bevy의 resource로 만들어 Bevy App에서 사용하도록 하였음. 
 /// 
-async fn connect_websocket() -> (tokio::sync::mpsc::Sender<String>, tokio::sync::mpsc::Receiver<Vec3>) {
-    println!("waiting for connecting to server!");
-    let (stream, res) = tokio_tungstenite::connect_async("ws://127.0.0.1:9003").await.unwrap();
+/// `wss://` 주소에 `ca_path`를 지정하면 해당 CA 인증서만 신뢰한다. (self-signed 인증서 테스트용)
+/// 지정하지 않으면 webpki 기본 root 인증서를 사용한다.
+/// `compression`이 true면 서버에게 압축된 frame을 받을 수 있다고 알린다.
+async fn connect_websocket(url: String, ca_path: Option<String>, token: Option<String>, compression: bool) -> (tokio::sync::mpsc::Sender<(ChannelKind, String)>, tokio::sync::mpsc::Receiver<ServerMessage>) {
+    println!("waiting for connecting to server! ({})", url);
+    let connector = ca_path.map(|ca_path| Connector::Rustls(Arc::new(load_client_tls_config(&ca_path))));
+    let request = websocket_request(&url, token.as_deref(), compression);
+    let (stream, res) = match tokio_tungstenite::connect_async_tls_with_config(request, None, false, connector).await {
+        Ok(result) => result,
+        Err(e) => exit_on_connect_error(e),
+    };
+
+    println!("websocket connect success!!, compression: {}", res.headers().contains_key(COMPRESSION_HEADER));
+
+    spawn_websocket_tasks(stream)
+}
+
+/// handshake 요청을 만든다. 토큰이 있으면 `Authorization: Bearer` header에 담는다.
+fn websocket_request(url: &str, token: Option<&str>, compression: bool) -> Request {
+    let mut request = url.into_client_request().expect("invalid websocket url");
+    if let Some(token) = token {
+        request.headers_mut().insert(AUTHORIZATION, format!("Bearer {}", token).parse().expect("invalid token"));
+    }
+    if compression {
+        request.headers_mut().insert(COMPRESSION_HEADER, DEFLATE.parse().unwrap());
+    }
+    request
+}
+
+/// 서버가 인증을 거절하면 401 응답 body에 사유를 담아 보내준다.
+fn exit_on_connect_error(error: tungstenite::Error) -> ! {
+    match error {
+        tungstenite::Error::Http(response) => {
+            let reason = response.body().as_deref().map(String::from_utf8_lossy).unwrap_or_default();
+            eprintln!("connection rejected by server ({}): {}", response.status(), reason);
+        },
+        e => eprintln!("fail to connect server, error: {}", e),
+    }
+    std::process::exit(1);
+}
+
+/// 지정한 CA 인증서(PEM)를 신뢰하는 rustls 클라이언트 설정
+fn load_client_tls_config(ca_path: &str) -> rustls::ClientConfig {
+    let mut roots = rustls::RootCertStore::empty();
+    for cert in CertificateDer::pem_file_iter(ca_path).expect("fail to read ca certificate") {
+        roots.add(cert.expect("invalid ca certificate")).expect("fail to add ca certificate");
+    }
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots)
+        .with_no_client_auth()
+}
+
+/// Unix domain socket으로 서버에 WebSocket 연결
+#[cfg(unix)]
+async fn connect_unix_websocket(token: Option<String>, compression: bool) -> (tokio::sync::mpsc::Sender<(ChannelKind, String)>, tokio::sync::mpsc::Receiver<ServerMessage>) {
+    println!("waiting for connecting to server! (unix socket)");
+    let unix_stream = tokio::net::UnixStream::connect(UNIX_SOCKET_PATH).await.unwrap();
+    let request = websocket_request("ws://localhost/", token.as_deref(), compression);
+    let (stream, _) = match tokio_tungstenite::client_async(request, unix_stream).await {
+        Ok(result) => result,
+        Err(e) => exit_on_connect_error(e),
+    };
 
     println!("websocket connect success!!");
 
+    spawn_websocket_tasks(stream)
+}
+
+#[cfg(not(unix))]
+async fn connect_unix_websocket(_token: Option<String>, _compression: bool) -> (tokio::sync::mpsc::Sender<(ChannelKind, String)>, tokio::sync::mpsc::Receiver<ServerMessage>) {
+    panic!("unix domain socket is not supported on this platform: {}", UNIX_SOCKET_PATH);
+}
+
+/// 연결된 WebSocket의 stream, sink를 처리하는 task를 각각 생성한다.
+fn spawn_websocket_tasks<S>(stream: WebSocketStream<S>) -> (tokio::sync::mpsc::Sender<(ChannelKind, String)>, tokio::sync::mpsc::Receiver<ServerMessage>)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
     let (mut sink, ws_stream) = stream.split();   
 
-    let (sender, mut receiver) = tokio::sync::mpsc::channel::<String>(10);
-    let (stream_sender, mut stream_recv) = tokio::sync::mpsc::channel::<Vec3>(10);
+    let (sender, mut receiver) = tokio::sync::mpsc::channel::<(ChannelKind, String)>(64);
+    let (stream_sender, mut stream_recv) = tokio::sync::mpsc::channel::<ServerMessage>(100);
 
     // start websocket stream receive task 
     tokio::spawn(async move {
@@ -111,41 +212,92 @@ async fn connect_websocket() -> (tokio::sync::mpsc::Sender<String>, tokio::sync:
 
 // websocket 받기
 // todo: websocket으로 받은 내용을 bevy에게 전달하는 로직이 필요함, 아직 구현되어있지 않음
-async fn handle_websocket_stream(stream: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>, tx: Sender<Vec3>) {
+async fn handle_websocket_stream<S>(mut stream: SplitStream<WebSocketStream<S>>, tx: Sender<ServerMessage>)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
     // websocket stream으로 받은 데이터를 처리하는 hander 
-    let future_stream = stream.try_for_each(|msg| {
-        let json_str = msg.to_text().unwrap();
-        let message: ServerMessage = serde_json::from_str(json_str).unwrap();
-        let tx_cloned = tx.clone();
-        match message {
-            ServerMessage::PlayerUpdate { translation } => {
-                tokio::spawn(async move {
-                    tx_cloned.send(translation).await.unwrap();
-                });
+    // 받은 순서대로 bevy에 전달해야 spawn 이전에 update가 처리되는 일이 없다.
+    let mut unreliable_filter = SequenceFilter::default();
+    let stats = CompressionStats::default();
+
+    while let Some(Ok(msg)) = stream.next().await {
+        // 압축된 frame은 binary 메시지로 온다.
+        let json_str = match msg {
+            Message::Close(frame) => {
+                println!("server closed the websocket connection, frame: {:?}", frame);
+                break;
+            },
+            Message::Binary(bytes) => {
+                let Some(decompressed) = ws_compression::decompress(&bytes, MAX_DECOMPRESSED_SIZE) else {
+                    eprintln!("[handle_websocket_stream] fail to decompress frame, size: {}", bytes.len());
+                    continue;
+                };
+                stats.record(decompressed.len(), bytes.len(), true);
+                match String::from_utf8(decompressed) {
+                    Ok(json_str) => json_str,
+                    Err(_) => continue,
+                }
+            },
+            Message::Text(text) => {
+                stats.record(text.len(), text.len(), false);
+                text.to_string()
             },
+            _ => continue,
+        };
+        // 서버는 한 번에 보낼 메시지들을 하나의 frame으로 묶어서 보낸다.
+        for message in decode_server_frame(&json_str, &mut unreliable_filter) {
+            if tx.send(message).await.is_err() {
+                return;
+            }
         }
-        
-        futures_util::future::ok(())
-    });
+    }
 
-    let _ = future_stream.await;
+    println!("[compression] {}", stats.summary());
+}
+
+/// 서버에서 받은 `Frame` json(packet 하나 또는 배열)을 `ServerMessage`들로 변환한다.
+/// 같은 엔티티의 더 최신 위치를 이미 받았다면 오래된 update는 버린다.
+fn decode_server_frame(json_str: &str, unreliable_filter: &mut SequenceFilter) -> Vec<ServerMessage> {
+    let frame: Frame<ServerMessage> = match serde_json::from_str(json_str) {
+        Ok(frame) => frame,
+        Err(e) => {
+            eprintln!("[decode_server_frame] invalid message, error: {}", e);
+            return Vec::new();
+        },
+    };
+
+    let mut messages = Vec::new();
+    for packet in frame.into_packets() {
+        if packet.channel == ChannelKind::UnreliableSequenced && !unreliable_filter.accept(packet.message.supersede_key(), packet.seq) {
+            continue;
+        }
+        if let ServerMessage::EntityDespawn { entity } = packet.message {
+            unreliable_filter.forget(entity);
+        }
+        messages.push(packet.message);
+    }
+    messages
 }
 
 /// websocket 보내기
 /// sink를 통해서 연결된 websocket server로 데이터를 보내는 handler 함수
 /// 
-async fn handle_websocket_sink(mut sink: SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>, mut receiver: tokio::sync::mpsc::Receiver<String>) {
+async fn handle_websocket_sink<S>(mut sink: SplitSink<WebSocketStream<S>, Message>, mut receiver: tokio::sync::mpsc::Receiver<(ChannelKind, String)>)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
     // mpsc receiver를 통해서 받은 데이터를 websocker sink로 보내는 handler 
     loop {
         match receiver.recv().await {
-            Some(msg) => {
+            Some((_, msg)) => {
                 // msg의 헤더에 따라서 
                 // 보내는 데이터가 달라진다.
-                println!("msg: {}", msg);
                 let _ = sink.send(Message::text(msg)).await;
             },
             None => {
-    
+                // bevy App이 종료되어 채널이 닫힘
+                break;
             },
         }
     }
@@ -161,15 +313,22 @@ fn setup(
     // Camera setting
     commands.spawn(Camera2d);
 
-    commands.spawn((
-        // 1. 메시 컴포넌트: 어떤 모양을 그릴지 정의
-        Mesh2d(meshes.add(Circle::new(50.0))),
-        // 2. 재질 컴포넌트: 메시의 색상, 텍스처 등을 정의
-        MeshMaterial2d(materials.add(ColorMaterial::from_color(RED))),
-        // 3. 트랜스폼 컴포넌트: 엔티티의 위치, 회전, 크기를 정의
-        Transform::from_translation(Vec3::new(0.0, 0.0, 0.0)),
-        Ball,
-    ));
+    // 벽은 움직이지 않으므로 서버와 같은 배치(`ARENA_WALLS`)로 미리 그려둔다.
+    let wall_material = materials.add(ColorMaterial::from_color(GRAY));
+    for (center, half_extents) in ARENA_WALLS {
+        commands.spawn((
+            Mesh2d(meshes.add(Rectangle::from_size(half_extents * 2.0))),
+            MeshMaterial2d(wall_material.clone()),
+            Transform::from_translation(center.extend(-1.0)),
+        ));
+    }
+
+    // 공 엔티티는 서버로부터 spawn 메시지를 받았을 때 생성한다.
+    commands.insert_resource(BallAssets {
+        mesh: meshes.add(Circle::new(PLAYER_RADIUS)),
+        local_material: materials.add(ColorMaterial::from_color(RED)),
+        remote_material: materials.add(ColorMaterial::from_color(BLUE)),
+    });
 }
 
 // endregion: -- setup
@@ -182,79 +341,126 @@ fn ball_move_system() {
 }
 
 // keyboard input system
-fn keyboard_input_system(mut keyboard_events: EventReader<KeyboardInput>, mut send_event: EventWriter<SendEvent>, mut query: Query<&mut Transform, With<Ball>>) {
-    for event in keyboard_events.read() {
-        if event.state == ButtonState::Pressed {
-            println!("Key {:?} was pressed!", event.key_code);
-            // let mut transform = query.single_mut().unwrap();
-
-            // ------- key_code convert to move_direction 
-            let move_direction = match event.key_code {
-                KeyCode::ArrowUp => {
-                    // transform.translation.y += 10.0;
-                    Some(MoveDirection::Up)
-                },
-                KeyCode::ArrowDown => {
-                    // transform.translation.y -= 10.0;
-                    Some(MoveDirection::Down)
-                },
-                KeyCode::ArrowLeft => {
-                    // transform.translation.x -= 10.0;
-                    Some(MoveDirection::Left)
-                },
-                KeyCode::ArrowRight => {
-                    // transform.translation.x += 10.0;
-                    Some(MoveDirection::Right)
-                },
-                _ => {
-                    None
+/// 고정 tick마다 누르고 있는 방향키 상태를 tick 번호와 함께 서버로 보낸다.
+/// 패킷 하나가 유실돼도 되도록 최근 입력 `INPUT_REDUNDANCY`개를 같이 보낸다.
+/// tick은 시간 동기화(`TimeSyncPlugin`)로 서버 tick보다 조금 앞서 있다.
+/// 서버가 지연 보상으로 판정할 수 있도록 지금 화면에 보이는 서버 tick도 같이 보낸다.
+fn keyboard_input_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    tick: Res<ClientTick>,
+    estimate: Res<ServerTimeEstimate>,
+    time: Res<Time<Real>>,
+    mut history: ResMut<InputHistory>,
+    mut input_event: EventWriter<InputPacket>,
+) {
+    let mut input = PlayerInput::default();
+    for (key_code, button) in [
+        (KeyCode::ArrowUp, PlayerInput::UP),
+        (KeyCode::ArrowDown, PlayerInput::DOWN),
+        (KeyCode::ArrowLeft, PlayerInput::LEFT),
+        (KeyCode::ArrowRight, PlayerInput::RIGHT),
+    ] {
+        if keys.pressed(key_code) {
+            input.buttons |= button;
+        }
+    }
+
+    history.0.push_front(input);
+    history.0.truncate(INPUT_REDUNDANCY);
+
+    let view_tick = estimate.view_tick(time.elapsed_secs_f64());
+    input_event.write(InputPacket { tick: tick.0, inputs: history.0.iter().copied().collect(), view_tick });
+}
+
+// 서버에서 보내준 메시지로
+// 엔티티 생성, 제거, 위치를 동기화해주는 시스템
+fn server_message_system(
+    mut commands: Commands,
+    mut receiver: ResMut<WebsocketStreamReceiver>,
+    mut entity_map: ResMut<NetworkEntityMap>,
+    mut local_player: ResMut<LocalPlayer>,
+    ball_assets: Res<BallAssets>,
+    mut query: Query<&mut Transform, With<Ball>>,
+    replicated_resources: Res<ReplicatedResources>,
+    server_events: Res<ServerEvents>,
+    mut app_exit: EventWriter<AppExit>,
+) {
+    while let Ok(msg) = receiver.0.try_recv() {
+        match msg {
+            ServerMessage::Welcome { entity } => {
+                local_player.0 = Some(entity);
+                // spawn 메시지가 먼저 도착한 경우
+                if let Some(&local) = entity_map.0.get(&entity) {
+                    commands.entity(local).insert((LocalBall, MeshMaterial2d(ball_assets.local_material.clone())));
+                }
+            },
+            ServerMessage::EntitySpawn { entity, translation, rotation, scale, parent } => {
+                let is_local = local_player.0 == Some(entity);
+                let material = if is_local { ball_assets.local_material.clone() } else { ball_assets.remote_material.clone() };
+
+                let mut local = commands.spawn((
+                    Mesh2d(ball_assets.mesh.clone()),
+                    MeshMaterial2d(material),
+                    Transform { translation, rotation, scale },
+                    Ball,
+                ));
+                if is_local {
+                    local.insert(LocalBall);
+                }
+                // 부모의 spawn 메시지가 항상 먼저 오므로 매핑된 부모 밑에 붙인다.
+                if let Some(&local_parent) = parent.and_then(|parent| entity_map.0.get(&parent)) {
+                    local.insert(ChildOf(local_parent));
                 }
-            };
 
-            if let Some(direction) = move_direction {
-                send_event.write(SendEvent(direction));    
-            }
+                entity_map.0.insert(entity, local.id());
+            },
+            ServerMessage::EntityDespawn { entity } => {
+                // 부모가 먼저 제거되면 자식도 같이 제거되므로 이미 없는 엔티티일 수 있다.
+                if let Some(local) = entity_map.0.remove(&entity) {
+                    commands.entity(local).try_despawn();
+                }
+            },
+            ServerMessage::PlayerUpdate { entity, translation, rotation, scale } => {
+                let Some(&local) = entity_map.0.get(&entity) else {
+                    continue;
+                };
+                if let Ok(mut transform) = query.get_mut(local) {
+                    // 바뀐 필드만 온다.
+                    if let Some(translation) = translation {
+                        transform.translation = translation;
+                    }
+                    if let Some(rotation) = rotation {
+                        transform.rotation = rotation;
+                    }
+                    if let Some(scale) = scale {
+                        transform.scale = scale;
+                    }
+                }
+            },
+            ServerMessage::Rejected { reason } => {
+                eprintln!("connection rejected by server: {}", reason);
+                app_exit.write(AppExit::error());
+            },
+            ServerMessage::ResourceUpdate { name, value } => {
+                replicated_resources.apply(&mut commands, &name, value);
+            },
+            ServerMessage::Event { name, value } => {
+                server_events.apply(&mut commands, &name, value);
+            },
         }
     }
 }
 
-/// 키보드 input event receiver handler system
-/// tokio runtime handle을 이용하여 send task를 생성해 준다. 
-/// 미리 생성해둔 resouce인 WebSocketChannelSender로 보내주면 됨.
-fn send_event_system(mut send_event: EventReader<SendEvent>, websocket_sender: Res<WebsocketChannelSender>, handle: Res<TokioRuntimeHandle>) {    
-    for event in send_event.read() {
-        // event 발생 시 websocket을 통해서 server로 보내준다.
-        // handle을 이용해줘야하는 듯? 
-        let directino_str = event.0.to_string();
-        let sender_clone = websocket_sender.0.clone();
-        handle.0.spawn(async move {
-            // msg 생성 필요
-            // up, down, left, right 
-            match sender_clone.send(directino_str).await {
-                Ok(_) => {
-                    println!("send success!!");
-                },
-                Err(e) => {
-                    eprintln!("[send_event_system] fail to send, error: {}", e);
-                },
-            };
-        });
+/// 서버에서 복제된 경기 정보가 바뀌면 출력한다.
+fn match_info_system(match_info: Option<Res<MatchInfo>>) {
+    if let Some(match_info) = match_info.filter(|match_info| match_info.is_changed()) {
+        println!("[match] map: {}, elapsed: {}s", match_info.map_name, match_info.elapsed_secs);
     }
 }
 
-// 서버에서 보내준 위치 정보를 
-// 동기화해주는 시스템
-fn move_sync_system(mut receiver: ResMut<WebsocketStreamReceiver>, mut query: Query<(&mut Transform), With<Ball>>) {
-    match receiver.0.try_recv() {
-        Ok(msg) => {
-            // 동기화를 해줘야함..
-            for mut transform in query.iter_mut() {
-                transform.translation = msg;
-            }
-        },
-        Err(_) => {
-
-        },
+fn player_joined_system(mut player_joined: EventReader<PlayerJoined>) {
+    for event in player_joined.read() {
+        println!("[match] {} joined", event.account);
     }
 }
 // endregion: -- system
\ No newline at end of file
@@ -0,0 +1,172 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::common::{TimePing, TimePong, TICK_RATE};
+
+use super::event::{ClientEventAppExt, ServerEventAppExt};
+
+/// 동기화에 사용하는 최근 측정값 수
+const MAX_SAMPLES: usize = 16;
+/// 이만큼 측정한 뒤부터 추정값을 사용한다.
+const MIN_SAMPLES: usize = 4;
+/// 동기화 전, 후 ping 주기 (초)
+const FAST_PING_INTERVAL: f64 = 0.2;
+const PING_INTERVAL: f64 = 1.0;
+/// 서버 입력 버퍼에 미리 쌓아둘 tick 수
+const INPUT_BUFFER_TICKS: f64 = 2.0;
+/// 목표 tick과 이 이상 차이 나면 속도를 조절하지 않고 바로 맞춘다.
+const MAX_TICK_ERROR: f64 = TICK_RATE / 2.0;
+/// 고정 tick 속도 조절 범위 (±5%)
+const MAX_TIMESTEP_ADJUST: f64 = 0.05;
+
+/// 서버 시간 동기화
+/// ping(`TimePing`)을 보내고 서버가 바로 돌려준 `TimePong`으로 왕복 시간(RTT), 시계 차이(offset), 시계 속도 차이(drift)를 추정한다.
+/// 클라이언트 tick은 보낸 입력이 서버에 도착했을 때 서버 입력 버퍼에 조금 쌓여 있도록
+/// 서버 tick보다 `RTT / 2 + INPUT_BUFFER_TICKS`만큼 앞서게 고정 tick 속도를 조절한다.
+pub struct TimeSyncPlugin;
+
+impl Plugin for TimeSyncPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ServerTimeEstimate>()
+            .init_resource::<ClientTick>()
+            .add_client_event::<TimePing>()
+            .add_server_event::<TimePong>()
+            .add_systems(FixedFirst, advance_tick_system)
+            .add_systems(Update, (
+                time_ping_system,
+                time_pong_system,
+                adjust_timestep_system,
+            ).chain());
+    }
+}
+
+// ----------------- resource
+
+/// 클라이언트 고정 tick 번호, 동기화된 뒤에는 서버 tick보다 조금 앞선다.
+#[derive(Resource, Default, Debug, Clone, Copy)]
+pub struct ClientTick(pub u32);
+
+/// 서버 시간 추정값, 시간은 모두 초 단위
+/// 서버 시간 = 클라이언트 시간 + offset + drift * (클라이언트 시간 - reference)
+#[derive(Resource, Default, Debug)]
+pub struct ServerTimeEstimate {
+    pub rtt: f64,
+    pub offset: f64,
+    pub drift: f64,
+    reference: f64,
+    /// (받은 시각, offset, rtt)
+    samples: VecDeque<(f64, f64, f64)>,
+    last_ping: Option<f64>,
+}
+
+impl ServerTimeEstimate {
+    pub fn synced(&self) -> bool {
+        self.samples.len() >= MIN_SAMPLES
+    }
+
+    pub fn server_time(&self, client_time: f64) -> f64 {
+        client_time + self.offset + self.drift * (client_time - self.reference)
+    }
+
+    /// 현재 서버 tick (소수점 포함)
+    pub fn server_tick(&self, client_time: f64) -> f64 {
+        self.server_time(client_time) * TICK_RATE
+    }
+
+    /// 지금 화면에 보이는 서버 상태의 tick, 서버에서 보낸 상태는 RTT의 절반 뒤에 도착한다.
+    /// 서버가 지연 보상으로 판정할 때 사용한다. (`InputPacket::view_tick`)
+    pub fn view_tick(&self, client_time: f64) -> f32 {
+        (self.server_tick(client_time) - self.rtt / 2.0 * TICK_RATE) as f32
+    }
+
+    /// 지금 보내는 입력에 붙일 tick
+    pub fn target_tick(&self, client_time: f64) -> f64 {
+        self.server_tick(client_time) + self.rtt / 2.0 * TICK_RATE + INPUT_BUFFER_TICKS
+    }
+
+    fn add_sample(&mut self, pong: &TimePong, now: f64) {
+        let rtt = (now - pong.client_time).max(0.0);
+        let server_time = (pong.server_tick as f64 + pong.overstep as f64) / TICK_RATE;
+        // 응답은 서버에서 RTT의 절반 전에 보냈다고 가정한다.
+        let offset = server_time + rtt / 2.0 - now;
+
+        self.samples.push_back((now, offset, rtt));
+        if self.samples.len() > MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+
+        // RTT가 중간값보다 큰 측정값은 지연이 튄 것이므로 제외한다.
+        let mut rtts: Vec<f64> = self.samples.iter().map(|sample| sample.2).collect();
+        rtts.sort_by(f64::total_cmp);
+        let median_rtt = rtts[rtts.len() / 2];
+        let filtered: Vec<(f64, f64)> = self.samples.iter()
+            .filter(|sample| sample.2 <= median_rtt)
+            .map(|sample| (sample.0, sample.1))
+            .collect();
+
+        self.rtt = median_rtt;
+        (self.reference, self.offset, self.drift) = fit_line(&filtered);
+    }
+}
+
+/// (x, y)의 최소제곱 직선, (x 평균, x 평균에서의 y, 기울기)
+fn fit_line(points: &[(f64, f64)]) -> (f64, f64, f64) {
+    let count = points.len() as f64;
+    let mean_x = points.iter().map(|point| point.0).sum::<f64>() / count;
+    let mean_y = points.iter().map(|point| point.1).sum::<f64>() / count;
+
+    let variance: f64 = points.iter().map(|point| (point.0 - mean_x).powi(2)).sum();
+    let covariance: f64 = points.iter().map(|point| (point.0 - mean_x) * (point.1 - mean_y)).sum();
+    let slope = if variance > f64::EPSILON { covariance / variance } else { 0.0 };
+
+    (mean_x, mean_y, slope)
+}
+
+// ----------------- system
+
+fn advance_tick_system(mut tick: ResMut<ClientTick>, estimate: Res<ServerTimeEstimate>, time: Res<Time<Real>>) {
+    tick.0 = tick.0.wrapping_add(1);
+
+    if !estimate.synced() {
+        return;
+    }
+    let target = estimate.target_tick(time.elapsed_secs_f64());
+    if (target - tick.0 as f64).abs() > MAX_TICK_ERROR {
+        println!("[time sync] client tick {} -> {}", tick.0, target.round());
+        tick.0 = target.round().max(0.0) as u32;
+    }
+}
+
+fn time_ping_system(mut estimate: ResMut<ServerTimeEstimate>, time: Res<Time<Real>>, mut pings: EventWriter<TimePing>) {
+    let now = time.elapsed_secs_f64();
+    let interval = if estimate.synced() { PING_INTERVAL } else { FAST_PING_INTERVAL };
+    if estimate.last_ping.is_some_and(|last_ping| now - last_ping < interval) {
+        return;
+    }
+
+    estimate.last_ping = Some(now);
+    pings.write(TimePing { client_time: now });
+}
+
+fn time_pong_system(mut estimate: ResMut<ServerTimeEstimate>, time: Res<Time<Real>>, mut pongs: EventReader<TimePong>) {
+    let was_synced = estimate.synced();
+    for pong in pongs.read() {
+        estimate.add_sample(pong, time.elapsed_secs_f64());
+    }
+
+    if !was_synced && estimate.synced() {
+        println!("[time sync] synced, rtt: {:.1}ms, offset: {:.3}s", estimate.rtt * 1000.0, estimate.offset);
+    }
+}
+
+/// 목표 tick보다 뒤처지면 고정 tick을 조금 빠르게, 앞서면 조금 느리게 돌린다.
+fn adjust_timestep_system(tick: Res<ClientTick>, estimate: Res<ServerTimeEstimate>, time: Res<Time<Real>>, mut fixed_time: ResMut<Time<Fixed>>) {
+    if !estimate.synced() {
+        return;
+    }
+
+    let error = estimate.target_tick(time.elapsed_secs_f64()) - tick.0 as f64;
+    let speed = 1.0 + (error * 0.01).clamp(-MAX_TIMESTEP_ADJUST, MAX_TIMESTEP_ADJUST);
+    fixed_time.set_timestep_hz(TICK_RATE * speed);
+}
@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::sync::mpsc::error::TrySendError;
+
+use crate::common::{ChannelKind, ClientMessage, NetworkMessage};
+
+use super::WebsocketChannelSender;
+
+/// 서버에서 보내는 event 등록
+/// `app.add_server_event::<T>()`로 등록한 타입만 `ServerMessage::Event`를 받아서 `EventReader<T>`로 읽을 수 있게 보낸다.
+/// 같은 이름을 두 번 등록하면 어느 타입으로 받을지 알 수 없으므로 panic한다.
+pub trait ServerEventAppExt {
+    fn add_server_event<T: Event + DeserializeOwned + NetworkMessage>(&mut self) -> &mut Self;
+}
+
+impl ServerEventAppExt for App {
+    fn add_server_event<T: Event + DeserializeOwned + NetworkMessage>(&mut self) -> &mut Self {
+        self.add_event::<T>().init_resource::<ServerEvents>();
+        let previous = self.world_mut().resource_mut::<ServerEvents>().0.insert(T::NAME, write_event::<T>);
+        assert!(previous.is_none(), "server event name is already registered: {}", T::NAME);
+        self
+    }
+}
+
+/// 클라이언트 -> 서버 event 등록
+/// `app.add_client_event::<T>()`로 등록하면 `EventWriter<T>`로 보낸 event를 서버로 보낸다.
+/// 서버에서는 같은 타입을 `add_client_event`로 등록해서 `FromClient<T>`로 받는다.
+/// `add_client_event`는 `ReliableOrdered` 채널로 보내고, `add_client_event_on`으로 채널을 고를 수 있다. (UDP)
+pub trait ClientEventAppExt {
+    fn add_client_event<T: Event + Serialize + NetworkMessage>(&mut self) -> &mut Self;
+    fn add_client_event_on<T: Event + Serialize + NetworkMessage>(&mut self, channel: ChannelKind) -> &mut Self;
+}
+
+impl ClientEventAppExt for App {
+    fn add_client_event<T: Event + Serialize + NetworkMessage>(&mut self) -> &mut Self {
+        self.add_client_event_on::<T>(ChannelKind::ReliableOrdered)
+    }
+
+    fn add_client_event_on<T: Event + Serialize + NetworkMessage>(&mut self, channel: ChannelKind) -> &mut Self {
+        // Update에서 보낸 event를 같은 frame 안에 보내도록 PostUpdate에서 읽는다.
+        self.add_event::<T>()
+            .add_systems(PostUpdate, move |events: EventReader<T>, websocket_sender: Res<WebsocketChannelSender>| {
+                send_client_event_system(channel, events, websocket_sender)
+            })
+    }
+}
+
+// ----------------- resource
+
+/// event 이름별로 받은 값을 event로 보내는 함수
+#[derive(Resource, Default)]
+pub struct ServerEvents(HashMap<&'static str, fn(&mut Commands, serde_json::Value) -> Result<(), serde_json::Error>>);
+
+impl ServerEvents {
+    /// 등록되지 않은 event는 무시한다.
+    pub fn apply(&self, commands: &mut Commands, name: &str, value: serde_json::Value) {
+        let Some(write) = self.0.get(name) else {
+            eprintln!("[server_event] unregistered event: {}", name);
+            return;
+        };
+
+        if let Err(e) = write(commands, value) {
+            eprintln!("[server_event] failed to deserialize {}: {}", name, e);
+        }
+    }
+}
+
+fn write_event<T: Event + DeserializeOwned>(commands: &mut Commands, value: serde_json::Value) -> Result<(), serde_json::Error> {
+    commands.send_event(serde_json::from_value::<T>(value)?);
+    Ok(())
+}
+
+// ----------------- system
+
+/// 미리 생성해둔 resouce인 WebSocketChannelSender에 `try_send`로 바로 넣어준다. (event 순서 유지)
+/// writer task가 밀려서 채널이 가득 차면 event를 버린다.
+fn send_client_event_system<T: Event + Serialize + NetworkMessage>(channel: ChannelKind, mut events: EventReader<T>, websocket_sender: Res<WebsocketChannelSender>) {
+    for event in events.read() {
+        let value = match serde_json::to_value(event) {
+            Ok(value) => value,
+            Err(e) => {
+                eprintln!("[client_event] failed to serialize {}: {}", T::NAME, e);
+                continue;
+            },
+        };
+        let client_msg = ClientMessage { name: T::NAME.to_string(), value };
+
+        match websocket_sender.0.try_send((channel, serde_json::to_string(&client_msg).unwrap())) {
+            Ok(_) => {},
+            Err(TrySendError::Full((_, msg))) => {
+                eprintln!("[client_event] send channel is full, drop event: {}", msg);
+            },
+            Err(TrySendError::Closed(_)) => {
+                eprintln!("[client_event] connection closed");
+            },
+        };
+    }
+}
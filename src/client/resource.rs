@@ -0,0 +1,47 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::de::DeserializeOwned;
+
+use crate::common::NetworkMessage;
+
+/// 서버에서 복제되는 `Resource` 등록
+/// `app.replicate_resource::<R>()`로 등록한 타입만 `ServerMessage::ResourceUpdate`를 받아서 world에 넣는다.
+/// 같은 이름을 두 번 등록하면 어느 타입으로 받을지 알 수 없으므로 panic한다.
+pub trait ReplicateResourceExt {
+    fn replicate_resource<R: Resource + DeserializeOwned + NetworkMessage>(&mut self) -> &mut Self;
+}
+
+impl ReplicateResourceExt for App {
+    fn replicate_resource<R: Resource + DeserializeOwned + NetworkMessage>(&mut self) -> &mut Self {
+        self.init_resource::<ReplicatedResources>();
+        let previous = self.world_mut().resource_mut::<ReplicatedResources>().0.insert(R::NAME, insert_resource::<R>);
+        assert!(previous.is_none(), "resource name is already registered: {}", R::NAME);
+        self
+    }
+}
+
+// ----------------- resource
+
+/// resource 이름별로 받은 값을 world에 넣는 함수
+#[derive(Resource, Default)]
+pub struct ReplicatedResources(HashMap<&'static str, fn(&mut Commands, serde_json::Value) -> Result<(), serde_json::Error>>);
+
+impl ReplicatedResources {
+    /// 등록되지 않은 resource는 무시한다.
+    pub fn apply(&self, commands: &mut Commands, name: &str, value: serde_json::Value) {
+        let Some(insert) = self.0.get(name) else {
+            eprintln!("[replicate_resource] unregistered resource: {}", name);
+            return;
+        };
+
+        if let Err(e) = insert(commands, value) {
+            eprintln!("[replicate_resource] failed to deserialize {}: {}", name, e);
+        }
+    }
+}
+
+fn insert_resource<R: Resource + DeserializeOwned>(commands: &mut Commands, value: serde_json::Value) -> Result<(), serde_json::Error> {
+    commands.insert_resource(serde_json::from_value::<R>(value)?);
+    Ok(())
+}
@@ -0,0 +1,128 @@
+use std::time::{Duration, Instant};
+
+use tokio::{net::UdpSocket, sync::mpsc::{Receiver, Sender}};
+use uuid::Uuid;
+
+use crate::common::{udp::{UdpConnection, UdpPacket, MAX_PAYLOAD}, ChannelKind, ChannelSequencer, SequenceFilter, ServerMessage};
+
+use super::decode_server_frame;
+
+/// handshake 단계마다 응답을 기다리는 시간과 재시도 횟수
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_millis(500);
+const HANDSHAKE_RETRIES: usize = 10;
+
+/// `connect_websocket`의 UDP 버전
+/// handshake가 끝나면 datagram을 처리하는 task를 생성하고 같은 형태의 채널을 반환한다.
+/// `addr`은 `host:port` 형식의 서버 주소이며, 서버가 인증을 거절하면 사유를 출력하고 종료한다.
+pub async fn connect_udp(addr: String, token: Option<String>) -> (Sender<(ChannelKind, String)>, Receiver<ServerMessage>) {
+    println!("waiting for connecting to server! (udp, {})", addr);
+    let server_addr = tokio::net::lookup_host(&addr).await.ok()
+        .and_then(|mut addrs| addrs.next())
+        .unwrap_or_else(|| panic!("invalid udp server address: {}", addr));
+    // 서버 주소와 같은 주소 체계로 bind해야 보낼 수 있다.
+    let local_addr = if server_addr.is_ipv6() { "[::]:0" } else { "0.0.0.0:0" };
+    let socket = UdpSocket::bind(local_addr).await.unwrap();
+    socket.connect(server_addr).await.unwrap();
+
+    if let Err(reason) = handshake(&socket, token.unwrap_or_default()).await {
+        eprintln!("udp handshake failed: {}", reason);
+        std::process::exit(1);
+    }
+    println!("udp connect success!!");
+
+    let (sender, receiver) = tokio::sync::mpsc::channel::<(ChannelKind, String)>(64);
+    let (stream_sender, stream_recv) = tokio::sync::mpsc::channel::<ServerMessage>(100);
+
+    tokio::spawn(async move {
+        println!("[Start] UDP Connection");
+        handle_udp_connection(socket, receiver, stream_sender).await;
+    });
+
+    (sender, stream_recv)
+}
+
+/// ConnectRequest -> Challenge -> ChallengeResponse(token) -> Accepted / Denied
+async fn handshake(socket: &UdpSocket, token: String) -> Result<(), String> {
+    let client_salt = Uuid::new_v4().as_u64_pair().0;
+    let mut request = UdpPacket::ConnectRequest { client_salt };
+    let mut buf = vec![0u8; 2048];
+
+    for _ in 0..HANDSHAKE_RETRIES {
+        socket.send(&request.encode()).await.map_err(|e| e.to_string())?;
+
+        let Ok(Ok(len)) = tokio::time::timeout(HANDSHAKE_TIMEOUT, socket.recv(&mut buf)).await else {
+            continue;
+        };
+
+        match UdpPacket::decode(&buf[..len]) {
+            Some(UdpPacket::Challenge { client_salt: salt, server_salt }) if salt == client_salt => {
+                request = UdpPacket::ChallengeResponse { client_salt, server_salt, token: token.clone() };
+            },
+            Some(UdpPacket::Accepted) => return Ok(()),
+            Some(UdpPacket::Denied { reason }) => return Err(format!("rejected by server: {}", reason)),
+            _ => {},
+        }
+    }
+
+    Err("no response from server".to_string())
+}
+
+async fn handle_udp_connection(socket: UdpSocket, mut receiver: Receiver<(ChannelKind, String)>, tx: Sender<ServerMessage>) {
+    let mut connection = UdpConnection::new(Instant::now());
+    let mut sequencer = ChannelSequencer::default();
+    let mut unreliable_filter = SequenceFilter::default();
+    let mut interval = tokio::time::interval(Duration::from_millis(10));
+    let mut buf = vec![0u8; 2048];
+
+    loop {
+        tokio::select! {
+            result = socket.recv(&mut buf) => {
+                let Ok(len) = result else {
+                    continue;
+                };
+                let Some(packet) = UdpPacket::decode(&buf[..len]) else {
+                    continue;
+                };
+                if let UdpPacket::Disconnect = packet {
+                    println!("server closed the udp connection");
+                    break;
+                }
+
+                for payload in connection.receive(packet, Instant::now()) {
+                    let Ok(json_str) = String::from_utf8(payload) else {
+                        continue;
+                    };
+                    for message in decode_server_frame(&json_str, &mut unreliable_filter) {
+                        if tx.send(message).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+            },
+            msg = receiver.recv() => {
+                let Some((channel, msg)) = msg else {
+                    break;
+                };
+                if msg.len() > MAX_PAYLOAD {
+                    eprintln!("[udp] message is too large to send, size: {}", msg.len());
+                    continue;
+                }
+                // 채널은 client event를 등록할 때 고른다. (`add_client_event_on`)
+                let datagram = connection.send(channel, sequencer.next(channel), msg.into_bytes(), Instant::now());
+                let _ = socket.send(&datagram).await;
+            },
+            _ = interval.tick() => {
+                let now = Instant::now();
+                if connection.is_timed_out(now) {
+                    println!("udp connection timed out");
+                    break;
+                }
+                for datagram in connection.poll(now) {
+                    let _ = socket.send(&datagram).await;
+                }
+            },
+        }
+    }
+
+    let _ = socket.send(&UdpPacket::Disconnect.encode()).await;
+}
@@ -1,11 +1,367 @@
+use std::collections::HashMap;
+
+
 use bevy::prelude::*;
 use serde::{Serialize, Deserialize};
 
+pub mod compression;
+pub mod quantize;
+pub mod udp;
+
+/// Unix domain socket 위의 WebSocket 경로
+pub const UNIX_SOCKET_PATH: &str = "/tmp/bevy_ecs_authoritative.sock";
+
+/// 서버, 클라이언트가 사용하는 전송 방식
+/// 네이티브 클라이언트는 UDP, 브라우저 클라이언트는 WebSocket을 사용한다.
+/// 서버에서는 플레이어 엔티티에 컴포넌트로 붙어서 어떤 전송 방식으로 접속했는지 알 수 있다.
+#[derive(Component, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransportKind {
+    /// ws://, port 9003
+    WebSocket,
+    /// port 9004
+    Udp,
+    /// `UNIX_SOCKET_PATH` 위의 WebSocket, 같은 머신의 봇이나 관리 도구용
+    UnixSocket,
+}
+
+impl From<&str> for TransportKind {
+    fn from(value: &str) -> Self {
+        match value {
+            "udp" => TransportKind::Udp,
+            "unix" => TransportKind::UnixSocket,
+            _ => TransportKind::WebSocket,
+        }
+    }
+}
+
+/// 서버 -> 클라이언트 메시지
+/// `entity`는 서버 `Entity`의 bits 값으로, 클라이언트는 이 값으로 자신의 엔티티와 매핑한다.
+/// 위치는 `quantize::DEFAULT_POSITION_QUANTIZATION`, 크기는 `quantize::DEFAULT_SCALE_QUANTIZATION` 단위의 정수로 보낸다.
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(tag = "type")]
 pub enum ServerMessage {
-    PlayerUpdate {
+    /// 접속한 클라이언트에게 자신의 플레이어 엔티티를 알려준다.
+    Welcome {
+        entity: u64,
+    },
+    /// 관심 영역에 새로 들어온 엔티티
+    /// `parent`가 있으면 `Transform`은 부모 기준이며, 부모의 spawn 메시지가 항상 먼저 도착한다.
+    EntitySpawn {
+        entity: u64,
+        #[serde(with = "quantize::translation")]
         translation: Vec3,
-    }, 
+        #[serde(with = "quantize::rotation")]
+        rotation: Quat,
+        #[serde(with = "quantize::scale")]
+        scale: Vec3,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        parent: Option<u64>,
+    },
+    /// 관심 영역에서 벗어났거나 제거된 엔티티
+    EntityDespawn {
+        entity: u64,
+    },
+    /// 마지막으로 보낸 뒤 바뀐 `Transform` 필드만 담는다.
+    PlayerUpdate {
+        entity: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none", with = "quantize::translation::option")]
+        translation: Option<Vec3>,
+        #[serde(default, skip_serializing_if = "Option::is_none", with = "quantize::rotation::option")]
+        rotation: Option<Quat>,
+        #[serde(default, skip_serializing_if = "Option::is_none", with = "quantize::scale::option")]
+        scale: Option<Vec3>,
+    },
+    /// 서버가 가득 차서 접속을 거절함, 이 메시지를 보낸 뒤 연결을 닫는다.
+    Rejected {
+        reason: String,
+    },
+    /// `replicate_resource`로 등록된 resource의 현재 값, 접속 직후와 값이 바뀔 때마다 보낸다.
+    /// `name`은 `NetworkMessage::NAME`
+    ResourceUpdate {
+        name: String,
+        value: serde_json::Value,
+    },
+    /// `add_server_event`로 등록된 event, 클라이언트에서는 `EventReader<T>`로 받는다.
+    /// `name`은 `NetworkMessage::NAME`
+    Event {
+        name: String,
+        value: serde_json::Value,
+    },
+}
+
+impl ServerMessage {
+    /// 메시지 종류별 기본 채널
+    /// 위치 update는 최신 값만 의미가 있으므로 `UnreliableSequenced`, 나머지는 반드시 도착해야 하므로 `ReliableOrdered`
+    pub fn default_channel(&self) -> ChannelKind {
+        match self {
+            ServerMessage::PlayerUpdate { .. } => ChannelKind::UnreliableSequenced,
+            _ => ChannelKind::ReliableOrdered,
+        }
+    }
+
+    /// `UnreliableSequenced` 채널에서 아직 보내지 않은 이전 메시지를 대체할 때 사용하는 key
+    pub fn supersede_key(&self) -> Option<u64> {
+        match self {
+            ServerMessage::PlayerUpdate { entity, .. } => Some(*entity),
+            _ => None,
+        }
+    }
+
+    /// 같은 `supersede_key`를 가진 더 최신 메시지를 합친다. 필드마다 최신 값으로 덮어쓴다.
+    pub fn coalesce(&mut self, newer: ServerMessage) {
+        match (self, newer) {
+            (
+                ServerMessage::PlayerUpdate { translation, rotation, scale, .. },
+                ServerMessage::PlayerUpdate { translation: newer_translation, rotation: newer_rotation, scale: newer_scale, .. },
+            ) => {
+                // 이전 메시지에만 있는 필드는 아직 보내지 않았으므로 유지한다.
+                *translation = newer_translation.or(*translation);
+                *rotation = newer_rotation.or(*rotation);
+                *scale = newer_scale.or(*scale);
+            },
+            (current, newer) => *current = newer,
+        }
+    }
+}
+
+/// 네트워크로 보내는 resource, event 타입의 이름
+/// 타입 경로(`type_name`)는 모듈을 옮기거나 컴파일러 버전에 따라 바뀔 수 있으므로 서버, 클라이언트가 같이 쓰는 이름을 직접 정한다.
+pub trait NetworkMessage {
+    const NAME: &'static str;
+}
+
+/// 모든 클라이언트에게 복제되는 경기 정보
+#[derive(Resource, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MatchInfo {
+    pub map_name: String,
+    /// 서버가 시작된 뒤 지난 시간(초)
+    pub elapsed_secs: u32,
+}
+
+impl NetworkMessage for MatchInfo {
+    const NAME: &'static str = "MatchInfo";
+}
+
+/// 클라이언트 -> 서버 메시지
+/// `add_client_event`로 등록된 event, `name`은 `NetworkMessage::NAME`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ClientMessage {
+    pub name: String,
+    pub value: serde_json::Value,
+}
+
+/// 서버, 클라이언트의 고정 tick 주기 (`FixedUpdate`, Hz)
+/// 클라이언트는 tick마다 `PlayerInput`을 보내므로 서버의 초당 메시지 제한보다 낮아야 한다.
+pub const TICK_RATE: f64 = 30.0;
+
+/// 플레이어 이동 속도 (초당)
+pub const PLAYER_SPEED: f32 = 200.0;
+
+/// 플레이어 원의 반지름, 서버 충돌 판정과 클라이언트 그리기에 같이 사용한다.
+pub const PLAYER_RADIUS: f32 = 50.0;
+
+/// 월드 경계 (원점 기준 반 크기), 플레이어는 이 밖으로 나갈 수 없다.
+pub const WORLD_HALF_EXTENTS: Vec2 = Vec2::new(1000.0, 1000.0);
+
+/// 움직이지 않는 벽 (중심, 반 크기)
+pub const ARENA_WALLS: [(Vec2, Vec2); 4] = [
+    (Vec2::new(0.0, 500.0), Vec2::new(300.0, 25.0)),
+    (Vec2::new(0.0, -500.0), Vec2::new(300.0, 25.0)),
+    (Vec2::new(-500.0, 0.0), Vec2::new(25.0, 300.0)),
+    (Vec2::new(500.0, 0.0), Vec2::new(25.0, 300.0)),
+];
+
+/// 한 tick 동안 누르고 있는 버튼 상태
+/// 서버는 플레이어 엔티티의 컴포넌트로 가지고 있으며, 입력 버퍼에서 tick마다 하나씩 꺼내서 바꾼다.
+#[derive(Component, Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PlayerInput {
+    pub buttons: u8,
+}
+
+impl PlayerInput {
+    pub const UP: u8 = 1 << 0;
+    pub const DOWN: u8 = 1 << 1;
+    pub const LEFT: u8 = 1 << 2;
+    pub const RIGHT: u8 = 1 << 3;
+
+    pub fn pressed(&self, button: u8) -> bool {
+        self.buttons & button != 0
+    }
+
+    /// 이동 방향, 대각선도 같은 속도가 되도록 정규화한다. 반대 방향을 같이 누르면 상쇄된다.
+    pub fn axis(&self) -> Vec2 {
+        let mut axis = Vec2::ZERO;
+        if self.pressed(PlayerInput::UP) {
+            axis.y += 1.0;
+        }
+        if self.pressed(PlayerInput::DOWN) {
+            axis.y -= 1.0;
+        }
+        if self.pressed(PlayerInput::LEFT) {
+            axis.x -= 1.0;
+        }
+        if self.pressed(PlayerInput::RIGHT) {
+            axis.x += 1.0;
+        }
+        axis.normalize_or_zero()
+    }
+}
+
+/// 입력 패킷마다 같이 보내는 최근 입력 수, 패킷 하나가 유실돼도 다음 패킷으로 복구된다.
+pub const INPUT_REDUNDANCY: usize = 3;
+
+/// 클라이언트가 고정 tick마다 보내는 입력, client event로 보낸다.
+#[derive(Event, Serialize, Deserialize, Debug, Clone)]
+pub struct InputPacket {
+    /// `inputs[0]`의 클라이언트 tick
+    pub tick: u32,
+    /// 최신 입력부터 최대 `INPUT_REDUNDANCY`개 (`tick`, `tick - 1`, ...)
+    pub inputs: Vec<PlayerInput>,
+    /// 입력을 만들 때 화면에 보이던 서버 tick (`ServerTimeEstimate::view_tick`), 서버는 지연 보상 판정에 사용한다.
+    #[serde(default)]
+    pub view_tick: f32,
+}
+
+impl NetworkMessage for InputPacket {
+    const NAME: &'static str = "InputPacket";
+}
+
+/// 시간 동기화 요청 (클라이언트 -> 서버)
+#[derive(Event, Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct TimePing {
+    /// 보낸 시각 (클라이언트 시간, 초)
+    pub client_time: f64,
+}
+
+impl NetworkMessage for TimePing {
+    const NAME: &'static str = "TimePing";
+}
+
+/// 시간 동기화 응답 (서버 -> 클라이언트)
+/// 서버 시간(초)은 `(server_tick + overstep) / TICK_RATE`
+#[derive(Event, Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct TimePong {
+    pub client_time: f64,
+    pub server_tick: u32,
+    /// 다음 고정 tick까지 진행된 비율 (0..1)
+    pub overstep: f32,
+}
+
+impl NetworkMessage for TimePong {
+    const NAME: &'static str = "TimePong";
+}
+
+/// 다른 플레이어가 접속했을 때 서버가 보내는 event
+#[derive(Event, Serialize, Deserialize, Debug, Clone)]
+pub struct PlayerJoined {
+    pub account: String,
+}
+
+impl NetworkMessage for PlayerJoined {
+    const NAME: &'static str = "PlayerJoined";
+}
+
+// region: -- channel
+
+/// 메시지 전송 채널 종류
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChannelKind {
+    /// 반드시 도착하고 보낸 순서대로 처리된다. (spawn, despawn, chat ...)
+    ReliableOrdered,
+    /// 반드시 도착하지만 순서는 보장하지 않는다.
+    ReliableUnordered,
+    /// 유실될 수 있고, 더 최신 메시지를 이미 받았다면 오래된 메시지는 버린다. (위치 update)
+    UnreliableSequenced,
+}
+
+impl ChannelKind {
+    fn index(self) -> usize {
+        match self {
+            ChannelKind::ReliableOrdered => 0,
+            ChannelKind::ReliableUnordered => 1,
+            ChannelKind::UnreliableSequenced => 2,
+        }
+    }
+}
+
+/// 실제로 주고받는 단위, 채널과 채널별 순서 번호가 붙는다.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Packet<T> {
+    pub channel: ChannelKind,
+    pub seq: u32,
+    pub message: T,
+}
+
+/// 한 frame(datagram)에 담긴 packet
+/// WebSocket writer task는 송신 대기열에서 한 번에 꺼낸 메시지들을 json 배열 하나(`Batch`)로 보낸다.
+/// UDP는 메시지마다 채널별 재전송이 필요하므로 datagram 하나에 packet 하나(`Single`)를 보낸다.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum Frame<T> {
+    Batch(Vec<Packet<T>>),
+    Single(Packet<T>),
+}
+
+impl<T> Frame<T> {
+    pub fn into_packets(self) -> Vec<Packet<T>> {
+        match self {
+            Frame::Batch(packets) => packets,
+            Frame::Single(packet) => vec![packet],
+        }
+    }
+}
+
+/// 보내는 쪽에서 채널을 고른 뒤 writer task로 넘기는 메시지
+pub struct Outgoing<M> {
+    pub channel: ChannelKind,
+    /// `Packet`에 붙은 채널별 순서 번호, UDP 전송에서 재전송 및 순서 보장에 사용한다.
+    pub seq: u32,
+    /// `UnreliableSequenced`일 때 같은 key를 가진 아직 보내지 않은 메시지를 대체한다.
+    pub key: Option<u64>,
+    pub message: M,
+}
+
+/// 연결마다 채널별 다음 순서 번호
+#[derive(Component, Default)]
+pub struct ChannelSequencer([u32; 3]);
+
+impl ChannelSequencer {
+    pub fn next(&mut self, channel: ChannelKind) -> u32 {
+        let seq = &mut self.0[channel.index()];
+        let current = *seq;
+        *seq = seq.wrapping_add(1);
+        current
+    }
+}
+
+/// wrap-around를 고려하여 `seq`가 `other`보다 최신인지 비교한다.
+pub fn sequence_greater_than(seq: u32, other: u32) -> bool {
+    seq != other && seq.wrapping_sub(other) < u32::MAX / 2
+}
+
+/// 받는 쪽에서 `UnreliableSequenced` 채널의 오래된 메시지를 걸러낸다.
+/// 채널 순서 번호는 모든 엔티티가 같이 사용하므로 최신 여부는 `ServerMessage::supersede_key`(엔티티)마다 비교한다.
+#[derive(Default)]
+pub struct SequenceFilter {
+    latest: HashMap<Option<u64>, u32>,
+}
+
+impl SequenceFilter {
+    /// 처리해야 하는 메시지면 true, 같은 key로 이미 더 최신 메시지를 받았다면 false
+    pub fn accept(&mut self, key: Option<u64>, seq: u32) -> bool {
+        match self.latest.get(&key) {
+            Some(&latest) if !sequence_greater_than(seq, latest) => false,
+            _ => {
+                self.latest.insert(key, seq);
+                true
+            },
+        }
+    }
+
+    /// 사라진 엔티티의 기록을 지운다.
+    pub fn forget(&mut self, key: u64) {
+        self.latest.remove(&Some(key));
+    }
 }
 
+// endregion: -- channel
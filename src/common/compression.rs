@@ -0,0 +1,81 @@
+use std::{io::{Read, Write}, sync::atomic::{AtomicU64, Ordering}};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+
+/// WebSocket 메시지 압축
+///
+/// 클라이언트는 handshake 요청에 `COMPRESSION_HEADER: deflate`를 보내서 압축을 받을 수 있다고 알리고,
+/// 서버도 압축을 사용하면 같은 header를 응답에 담는다.
+/// 압축된 frame은 deflate로 압축한 binary 메시지, 압축하지 않은 frame은 지금처럼 text 메시지로 보낸다.
+pub const COMPRESSION_HEADER: &str = "x-bevy-compression";
+pub const DEFLATE: &str = "deflate";
+
+/// 보내는 쪽 압축 설정
+#[derive(Clone, Copy)]
+pub struct CompressionSettings {
+    /// false면 handshake에서 압축을 사용한다고 알리지 않는다.
+    pub enabled: bool,
+    /// 이 크기(바이트) 이상인 frame만 압축한다. 작은 frame은 압축해도 거의 줄지 않는다.
+    pub threshold: usize,
+}
+
+impl Default for CompressionSettings {
+    fn default() -> Self {
+        CompressionSettings { enabled: true, threshold: 256 }
+    }
+}
+
+/// 압축을 풀었을 때 허용하는 최대 크기, 작은 frame으로 메모리를 가득 채우는 공격을 막는다.
+pub const MAX_DECOMPRESSED_SIZE: usize = 16 * 1024 * 1024;
+
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+    // Vec에 쓰는 것은 실패하지 않는다.
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// 압축을 풀지 못하거나 `max_size`보다 크면 `None`
+pub fn decompress(data: &[u8], max_size: usize) -> Option<Vec<u8>> {
+    let mut decompressed = Vec::new();
+    DeflateDecoder::new(data).take(max_size as u64 + 1).read_to_end(&mut decompressed).ok()?;
+
+    (decompressed.len() <= max_size).then_some(decompressed)
+}
+
+/// 압축 전후 바이트 수
+/// 압축하지 않고 보낸 frame도 같이 세어서 전체 트래픽 대비 절약한 양을 볼 수 있다.
+#[derive(Default)]
+pub struct CompressionStats {
+    frames: AtomicU64,
+    compressed_frames: AtomicU64,
+    uncompressed_bytes: AtomicU64,
+    compressed_bytes: AtomicU64,
+}
+
+impl CompressionStats {
+    /// `uncompressed`: 원래 크기, `sent`: 실제로 주고받은 크기
+    pub fn record(&self, uncompressed: usize, sent: usize, compressed: bool) {
+        self.frames.fetch_add(1, Ordering::Relaxed);
+        if compressed {
+            self.compressed_frames.fetch_add(1, Ordering::Relaxed);
+        }
+        self.uncompressed_bytes.fetch_add(uncompressed as u64, Ordering::Relaxed);
+        self.compressed_bytes.fetch_add(sent as u64, Ordering::Relaxed);
+    }
+
+    pub fn summary(&self) -> String {
+        let uncompressed = self.uncompressed_bytes.load(Ordering::Relaxed);
+        let compressed = self.compressed_bytes.load(Ordering::Relaxed);
+        let ratio = if uncompressed == 0 { 100.0 } else { compressed as f64 * 100.0 / uncompressed as f64 };
+
+        format!(
+            "frames: {} (compressed: {}), bytes: {} -> {} ({:.1}%)",
+            self.frames.load(Ordering::Relaxed),
+            self.compressed_frames.load(Ordering::Relaxed),
+            uncompressed,
+            compressed,
+            ratio,
+        )
+    }
+}
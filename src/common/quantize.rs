@@ -0,0 +1,330 @@
+use bevy::prelude::*;
+
+/// 복제되는 transform 값 양자화
+///
+/// 위치, 크기는 `precision` 단위의 정수로, 회전은 smallest-three 방식으로 32bit 하나에 담는다.
+/// `translation`, `rotation`, `scale` serde 모듈을 사용하면 json에 소수 대신 짧은 정수로 직렬화되고,
+/// 서버는 양자화한 값이 바뀐 필드만 보낸다.
+/// 위치의 정밀도와 범위는 `PositionQuantizer`를 구현한 타입으로 필드마다 고를 수 있다. (`translation::serialize_as`)
+
+/// 위치 양자화 설정
+/// 각 축은 `-bound..=bound` 범위로 잘리고 `precision` 단위로 반올림된다.
+#[derive(Clone, Copy, Debug)]
+pub struct PositionQuantization {
+    pub precision: f32,
+    pub bound: f32,
+}
+
+/// 0.01 단위, 축마다 ±100,000 (2·10^7 단계, 축당 25bit)
+pub const DEFAULT_POSITION_QUANTIZATION: PositionQuantization = PositionQuantization { precision: 0.01, bound: 100_000.0 };
+
+/// 크기는 1 근처의 값이 대부분이라 위치와 같은 0.01 단위로는 1%씩밖에 바뀌지 않는다.
+/// 0.001 단위, 축마다 ±1,000 (2·10^6 단계, 축당 21bit)
+pub const DEFAULT_SCALE_QUANTIZATION: PositionQuantization = PositionQuantization { precision: 0.001, bound: 1_000.0 };
+
+/// serde 모듈에 넘길 위치 양자화 설정
+/// `#[serde(with = ...)]`에는 값을 넘길 수 없으므로 설정을 타입으로 만들어 `serialize_as::<Q, _>`로 고른다.
+pub trait PositionQuantizer {
+    const QUANTIZATION: PositionQuantization;
+}
+
+/// `DEFAULT_POSITION_QUANTIZATION`, `translation` 모듈의 `serialize`, `deserialize`가 사용한다.
+pub struct DefaultPosition;
+
+impl PositionQuantizer for DefaultPosition {
+    const QUANTIZATION: PositionQuantization = DEFAULT_POSITION_QUANTIZATION;
+}
+
+/// `DEFAULT_SCALE_QUANTIZATION`, `scale` 모듈이 사용한다.
+pub struct DefaultScale;
+
+impl PositionQuantizer for DefaultScale {
+    const QUANTIZATION: PositionQuantization = DEFAULT_SCALE_QUANTIZATION;
+}
+
+impl PositionQuantization {
+    pub fn quantize(&self, value: Vec3) -> IVec3 {
+        let clamped = value.clamp(Vec3::splat(-self.bound), Vec3::splat(self.bound));
+        (clamped / self.precision).round().as_ivec3()
+    }
+
+    pub fn dequantize(&self, value: IVec3) -> Vec3 {
+        value.as_vec3() * self.precision
+    }
+}
+
+/// smallest-three 회전 양자화에서 성분 하나에 사용하는 bit 수
+pub const ROTATION_COMPONENT_BITS: u32 = 10;
+
+/// 단위 quaternion에서 절댓값이 가장 큰 성분은 나머지 세 성분으로 복원할 수 있으므로
+/// 가장 큰 성분의 위치(2bit)와 나머지 세 성분(각 10bit)만 기록한다. (총 32bit)
+/// 나머지 성분은 항상 ±1/√2 범위에 있다.
+pub fn quantize_rotation(rotation: Quat) -> u32 {
+    let rotation = rotation.normalize();
+    let components = rotation.to_array();
+    let largest = (0..4).max_by(|a, b| components[*a].abs().total_cmp(&components[*b].abs())).unwrap();
+    // q와 -q는 같은 회전이므로 가장 큰 성분이 양수가 되도록 맞춘다.
+    let sign = components[largest].signum();
+
+    let max_value = ((1 << ROTATION_COMPONENT_BITS) - 1) as f32;
+    let mut packed = largest as u32;
+    for (index, component) in components.iter().enumerate() {
+        if index == largest {
+            continue;
+        }
+        let normalized = (component * sign * std::f32::consts::SQRT_2 + 1.0) * 0.5;
+        let quantized = (normalized.clamp(0.0, 1.0) * max_value).round() as u32;
+        packed = (packed << ROTATION_COMPONENT_BITS) | quantized;
+    }
+
+    packed
+}
+
+pub fn dequantize_rotation(packed: u32) -> Quat {
+    let max_value = ((1 << ROTATION_COMPONENT_BITS) - 1) as f32;
+    let mask = (1 << ROTATION_COMPONENT_BITS) - 1;
+    let largest = (packed >> (ROTATION_COMPONENT_BITS * 3)) as usize & 0b11;
+
+    let mut components = [0.0; 4];
+    let mut shift = ROTATION_COMPONENT_BITS * 3;
+    let mut sum_squares = 0.0;
+    for (index, component) in components.iter_mut().enumerate() {
+        if index == largest {
+            continue;
+        }
+        shift -= ROTATION_COMPONENT_BITS;
+        let quantized = (packed >> shift) & mask;
+        *component = (quantized as f32 / max_value * 2.0 - 1.0) / std::f32::consts::SQRT_2;
+        sum_squares += *component * *component;
+    }
+    components[largest] = (1.0 - sum_squares).max(0.0).sqrt();
+
+    Quat::from_array(components).normalize()
+}
+
+/// `#[serde(with = "quantize::translation")]`
+/// `DEFAULT_POSITION_QUANTIZATION` 단위의 정수 배열로 직렬화한다.
+/// 다른 설정을 사용하려면
+/// `#[serde(serialize_with = "quantize::translation::serialize_as::<Q, _>", deserialize_with = "quantize::translation::deserialize_as::<Q, _>")]`
+pub mod translation {
+    use bevy::prelude::*;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{DefaultPosition, PositionQuantizer};
+
+    pub fn serialize<S: Serializer>(value: &Vec3, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_as::<DefaultPosition, S>(value, serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec3, D::Error> {
+        deserialize_as::<DefaultPosition, D>(deserializer)
+    }
+
+    pub fn serialize_as<Q: PositionQuantizer, S: Serializer>(value: &Vec3, serializer: S) -> Result<S::Ok, S::Error> {
+        Q::QUANTIZATION.quantize(*value).to_array().serialize(serializer)
+    }
+
+    pub fn deserialize_as<'de, Q: PositionQuantizer, D: Deserializer<'de>>(deserializer: D) -> Result<Vec3, D::Error> {
+        let value = <[i32; 3]>::deserialize(deserializer)?;
+        Ok(Q::QUANTIZATION.dequantize(IVec3::from_array(value)))
+    }
+
+    /// 바뀐 필드만 보낼 때 사용하는 `Option<Vec3>` 버전
+    /// `#[serde(default, skip_serializing_if = "Option::is_none", with = "quantize::translation::option")]`
+    pub mod option {
+        use bevy::prelude::*;
+        use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+        use crate::common::quantize::{DefaultPosition, PositionQuantizer};
+
+        pub fn serialize<S: Serializer>(value: &Option<Vec3>, serializer: S) -> Result<S::Ok, S::Error> {
+            serialize_as::<DefaultPosition, S>(value, serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec3>, D::Error> {
+            deserialize_as::<DefaultPosition, D>(deserializer)
+        }
+
+        pub fn serialize_as<Q: PositionQuantizer, S: Serializer>(value: &Option<Vec3>, serializer: S) -> Result<S::Ok, S::Error> {
+            value.map(|value| Q::QUANTIZATION.quantize(value).to_array()).serialize(serializer)
+        }
+
+        pub fn deserialize_as<'de, Q: PositionQuantizer, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec3>, D::Error> {
+            let value = Option::<[i32; 3]>::deserialize(deserializer)?;
+            Ok(value.map(|value| Q::QUANTIZATION.dequantize(IVec3::from_array(value))))
+        }
+    }
+}
+
+/// `#[serde(with = "quantize::scale")]`
+/// `DEFAULT_SCALE_QUANTIZATION` 단위의 정수 배열로 직렬화한다.
+pub mod scale {
+    use bevy::prelude::*;
+    use serde::{Deserializer, Serializer};
+
+    use super::{translation, DefaultScale};
+
+    pub fn serialize<S: Serializer>(value: &Vec3, serializer: S) -> Result<S::Ok, S::Error> {
+        translation::serialize_as::<DefaultScale, S>(value, serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec3, D::Error> {
+        translation::deserialize_as::<DefaultScale, D>(deserializer)
+    }
+
+    /// 바뀐 필드만 보낼 때 사용하는 `Option<Vec3>` 버전
+    pub mod option {
+        use bevy::prelude::*;
+        use serde::{Deserializer, Serializer};
+
+        use crate::common::quantize::{translation, DefaultScale};
+
+        pub fn serialize<S: Serializer>(value: &Option<Vec3>, serializer: S) -> Result<S::Ok, S::Error> {
+            translation::option::serialize_as::<DefaultScale, S>(value, serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec3>, D::Error> {
+            translation::option::deserialize_as::<DefaultScale, D>(deserializer)
+        }
+    }
+}
+
+/// `#[serde(with = "quantize::rotation")]`
+/// smallest-three로 양자화한 u32 하나로 직렬화한다.
+pub mod rotation {
+    use bevy::prelude::*;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Quat, serializer: S) -> Result<S::Ok, S::Error> {
+        super::quantize_rotation(*value).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Quat, D::Error> {
+        Ok(super::dequantize_rotation(u32::deserialize(deserializer)?))
+    }
+
+    /// 바뀐 필드만 보낼 때 사용하는 `Option<Quat>` 버전
+    pub mod option {
+        use bevy::prelude::*;
+        use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+        use crate::common::quantize::{dequantize_rotation, quantize_rotation};
+
+        pub fn serialize<S: Serializer>(value: &Option<Quat>, serializer: S) -> Result<S::Ok, S::Error> {
+            value.map(quantize_rotation).serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Quat>, D::Error> {
+            Ok(Option::<u32>::deserialize(deserializer)?.map(dequantize_rotation))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 여러 축과 각도로 만든 회전, 가장 큰 성분이 음수인 경우도 포함한다.
+    fn sample_rotations() -> Vec<Quat> {
+        let mut rotations = vec![Quat::IDENTITY, Quat::from_xyzw(0.0, 0.0, 0.0, -1.0), Quat::from_xyzw(0.0, -1.0, 0.0, 0.0)];
+        for step in 0..64 {
+            let angle = step as f32 * 0.37 - 12.0;
+            let axis = Vec3::new((step as f32 * 1.3).sin(), (step as f32 * 0.7).cos(), step as f32 * 0.1 - 3.0).normalize();
+            let rotation = Quat::from_axis_angle(axis, angle);
+            rotations.push(rotation);
+            rotations.push(-rotation);
+        }
+        rotations
+    }
+
+    /// 가장 큰 성분의 위치는 위쪽 2bit, 나머지 세 성분은 순서대로 10bit씩 들어간다.
+    #[test]
+    fn rotation_bit_layout() {
+        let center = 1 << (ROTATION_COMPONENT_BITS - 1);
+        let expected = (3 << 30) | (center << 20) | (center << 10) | center;
+        assert_eq!(quantize_rotation(Quat::IDENTITY), expected);
+
+        let packed = quantize_rotation(Quat::from_xyzw(0.0, 1.0, 0.0, 0.0));
+        assert_eq!(packed >> 30, 1);
+        assert!(dequantize_rotation(packed).angle_between(Quat::from_xyzw(0.0, 1.0, 0.0, 0.0)).to_degrees() < 0.25);
+    }
+
+    /// q와 -q는 같은 회전이므로 같은 값으로 양자화되고, 복원하면 가장 큰 성분이 양수다.
+    #[test]
+    fn rotation_sign_is_normalized() {
+        for rotation in sample_rotations() {
+            let packed = quantize_rotation(rotation);
+            assert_eq!(packed, quantize_rotation(-rotation));
+
+            let restored = dequantize_rotation(packed);
+            let largest = (packed >> 30) as usize;
+            assert!(restored.to_array()[largest] >= 0.0);
+        }
+    }
+
+    /// 성분마다 오차는 최대 `1/√2 / 1023`이므로 회전각 오차는 0.25도 안쪽이다.
+    #[test]
+    fn rotation_round_trip_error_is_bounded() {
+        for rotation in sample_rotations() {
+            let restored = dequantize_rotation(quantize_rotation(rotation));
+            let error = rotation.angle_between(restored).to_degrees();
+            assert!(error < 0.25, "rotation: {:?}, restored: {:?}, error: {}", rotation, restored, error);
+        }
+    }
+
+    /// 범위 안의 위치는 `precision`의 절반 이하의 오차로 복원되고, 범위 밖의 위치는 `bound`로 잘린다.
+    #[test]
+    fn position_round_trip_and_clamp() {
+        let quantization = DEFAULT_POSITION_QUANTIZATION;
+        for value in [Vec3::ZERO, Vec3::new(0.004, -0.006, 12.345), Vec3::new(-99_999.99, 50_000.5, 1.0 / 3.0)] {
+            let restored = quantization.dequantize(quantization.quantize(value));
+            // f32 자체의 오차를 위해 조금 여유를 둔다.
+            let tolerance = quantization.precision * 0.5 + value.abs().max_element() * f32::EPSILON * 2.0;
+            assert!((restored - value).abs().max_element() <= tolerance, "value: {}, restored: {}", value, restored);
+        }
+
+        let bound = quantization.bound;
+        let clamped = quantization.dequantize(quantization.quantize(Vec3::new(1.0e9, -1.0e9, bound)));
+        assert_eq!(clamped, Vec3::new(bound, -bound, bound));
+    }
+
+    /// 0.001 단위, 축마다 ±10
+    struct Millimeter;
+
+    impl PositionQuantizer for Millimeter {
+        const QUANTIZATION: PositionQuantization = PositionQuantization { precision: 0.001, bound: 10.0 };
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Offset {
+        #[serde(serialize_with = "translation::serialize_as::<Millimeter, _>", deserialize_with = "translation::deserialize_as::<Millimeter, _>")]
+        value: Vec3,
+        #[serde(default, skip_serializing_if = "Option::is_none", serialize_with = "translation::option::serialize_as::<Millimeter, _>", deserialize_with = "translation::option::deserialize_as::<Millimeter, _>")]
+        changed: Option<Vec3>,
+    }
+
+    /// 필드마다 고른 `PositionQuantizer`의 정밀도와 범위로 직렬화된다.
+    #[test]
+    fn translation_uses_field_quantizer() {
+        let offset = Offset { value: Vec3::new(0.1234, -20.0, 1.0), changed: Some(Vec3::new(0.0005, 0.0, 0.0)) };
+        let json = serde_json::to_string(&offset).unwrap();
+        assert_eq!(json, r#"{"value":[123,-10000,1000],"changed":[1,0,0]}"#);
+
+        let restored: Offset = serde_json::from_str(&json).unwrap();
+        assert!((restored.value - Vec3::new(0.123, -10.0, 1.0)).abs().max_element() < 1.0e-6);
+        assert!((restored.changed.unwrap() - Vec3::new(0.001, 0.0, 0.0)).abs().max_element() < 1.0e-6);
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Scaled {
+        #[serde(with = "scale")]
+        scale: Vec3,
+    }
+
+    /// 크기는 위치보다 촘촘한 `DEFAULT_SCALE_QUANTIZATION` 단위로 보낸다.
+    #[test]
+    fn scale_uses_scale_quantization() {
+        let json = serde_json::to_string(&Scaled { scale: Vec3::new(1.0, 1.005, 0.5) }).unwrap();
+        assert_eq!(json, r#"{"scale":[1000,1005,500]}"#);
+    }
+}
@@ -0,0 +1,391 @@
+use std::{collections::{HashMap, HashSet}, time::{Duration, Instant}};
+
+use super::ChannelKind;
+
+/// UDP 전송 프로토콜
+///
+/// 모든 datagram은 `PROTOCOL_ID`(u32) + 종류(u8)로 시작한다.
+/// ## 연결 handshake
+/// 1. client -> server: `ConnectRequest { client_salt }`
+/// 2. server -> client: `Challenge { client_salt, server_salt }`
+/// 3. client -> server: `ChallengeResponse { client_salt, server_salt, token }`
+/// 4. server -> client: `Accepted`, 인증에 실패하면 `Denied { reason }`
+///
+/// 서버는 challenge에 응답한 주소만 연결로 인정하므로 주소를 위조한 요청으로는 엔티티가 생성되지 않는다.
+/// 인증 토큰은 challenge 응답에 담아 보내며, 토큰이 없으면 빈 문자열을 보낸다.
+///
+/// ## 데이터
+/// `Data`, `KeepAlive`는 `AckHeader`(packet 순서 번호 + 상대방 packet에 대한 ack)를 가진다.
+/// reliable 채널 메시지는 ack를 받을 때까지 `RESEND_TIMEOUT`마다 새 packet으로 다시 보낸다.
+pub const PROTOCOL_ID: u32 = 0x4245_5659;
+
+/// 한 datagram에 담을 수 있는 최대 payload 크기, fragmentation은 지원하지 않는다.
+/// 서버는 이보다 큰 reliable 메시지를 보내야 하면 순서가 끊기지 않도록 연결을 끊는다.
+pub const MAX_PAYLOAD: usize = 1200;
+
+/// `ReliableOrdered` 채널에서 앞선 메시지를 기다리며 보관할 수 있는 범위, 이보다 앞선 메시지는 버린다.
+/// 버린 메시지는 ack하지 않으므로 보내는 쪽이 나중에 다시 보낸다.
+pub const REORDER_WINDOW: u32 = 1024;
+
+pub const RESEND_TIMEOUT: Duration = Duration::from_millis(200);
+/// 받은 packet이 있는데 보낼 데이터가 없을 때 ack만 담아 보내는 간격
+pub const ACK_INTERVAL: Duration = Duration::from_millis(30);
+pub const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(1);
+pub const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy)]
+pub struct AckHeader {
+    /// 이 packet의 순서 번호
+    pub seq: u16,
+    /// 상대방에게서 받은 가장 최신 packet 순서 번호
+    pub ack: Option<u16>,
+    /// `ack` 이전 32개 packet의 수신 여부 (bit i = ack - (i + 1))
+    pub ack_bits: u32,
+}
+
+#[derive(Debug)]
+pub enum UdpPacket {
+    ConnectRequest { client_salt: u64 },
+    Challenge { client_salt: u64, server_salt: u64 },
+    ChallengeResponse { client_salt: u64, server_salt: u64, token: String },
+    Accepted,
+    Denied { reason: String },
+    Data { header: AckHeader, channel: ChannelKind, message_seq: u32, payload: Vec<u8> },
+    KeepAlive { header: AckHeader },
+    Disconnect,
+}
+
+impl UdpPacket {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = PROTOCOL_ID.to_be_bytes().to_vec();
+
+        match self {
+            UdpPacket::ConnectRequest { client_salt } => {
+                buf.push(0);
+                buf.extend_from_slice(&client_salt.to_be_bytes());
+            },
+            UdpPacket::Challenge { client_salt, server_salt } => {
+                buf.push(1);
+                buf.extend_from_slice(&client_salt.to_be_bytes());
+                buf.extend_from_slice(&server_salt.to_be_bytes());
+            },
+            UdpPacket::ChallengeResponse { client_salt, server_salt, token } => {
+                buf.push(2);
+                buf.extend_from_slice(&client_salt.to_be_bytes());
+                buf.extend_from_slice(&server_salt.to_be_bytes());
+                buf.extend_from_slice(token.as_bytes());
+            },
+            UdpPacket::Accepted => buf.push(3),
+            UdpPacket::Data { header, channel, message_seq, payload } => {
+                buf.push(4);
+                encode_header(&mut buf, header);
+                buf.push(channel_to_u8(*channel));
+                buf.extend_from_slice(&message_seq.to_be_bytes());
+                buf.extend_from_slice(payload);
+            },
+            UdpPacket::KeepAlive { header } => {
+                buf.push(5);
+                encode_header(&mut buf, header);
+            },
+            UdpPacket::Disconnect => buf.push(6),
+            UdpPacket::Denied { reason } => {
+                buf.push(7);
+                buf.extend_from_slice(reason.as_bytes());
+            },
+        }
+
+        buf
+    }
+
+    /// 프로토콜이 다르거나 잘린 datagram이면 `None`
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut reader = Reader(bytes);
+
+        if reader.u32()? != PROTOCOL_ID {
+            return None;
+        }
+
+        let packet = match reader.u8()? {
+            0 => UdpPacket::ConnectRequest { client_salt: reader.u64()? },
+            1 => UdpPacket::Challenge { client_salt: reader.u64()?, server_salt: reader.u64()? },
+            2 => UdpPacket::ChallengeResponse {
+                client_salt: reader.u64()?,
+                server_salt: reader.u64()?,
+                token: String::from_utf8(reader.0.to_vec()).ok()?,
+            },
+            3 => UdpPacket::Accepted,
+            4 => UdpPacket::Data {
+                header: decode_header(&mut reader)?,
+                channel: channel_from_u8(reader.u8()?)?,
+                message_seq: reader.u32()?,
+                payload: reader.0.to_vec(),
+            },
+            5 => UdpPacket::KeepAlive { header: decode_header(&mut reader)? },
+            6 => UdpPacket::Disconnect,
+            7 => UdpPacket::Denied { reason: String::from_utf8_lossy(reader.0).into_owned() },
+            _ => return None,
+        };
+
+        Some(packet)
+    }
+}
+
+fn channel_to_u8(channel: ChannelKind) -> u8 {
+    match channel {
+        ChannelKind::ReliableOrdered => 0,
+        ChannelKind::ReliableUnordered => 1,
+        ChannelKind::UnreliableSequenced => 2,
+    }
+}
+
+fn channel_from_u8(value: u8) -> Option<ChannelKind> {
+    match value {
+        0 => Some(ChannelKind::ReliableOrdered),
+        1 => Some(ChannelKind::ReliableUnordered),
+        2 => Some(ChannelKind::UnreliableSequenced),
+        _ => None,
+    }
+}
+
+fn encode_header(buf: &mut Vec<u8>, header: &AckHeader) {
+    buf.extend_from_slice(&header.seq.to_be_bytes());
+    match header.ack {
+        Some(ack) => {
+            buf.push(1);
+            buf.extend_from_slice(&ack.to_be_bytes());
+        },
+        None => {
+            buf.push(0);
+            buf.extend_from_slice(&0u16.to_be_bytes());
+        },
+    }
+    buf.extend_from_slice(&header.ack_bits.to_be_bytes());
+}
+
+fn decode_header(reader: &mut Reader) -> Option<AckHeader> {
+    let seq = reader.u16()?;
+    let has_ack = reader.u8()? == 1;
+    let ack = reader.u16()?;
+    let ack_bits = reader.u32()?;
+
+    Some(AckHeader { seq, ack: has_ack.then_some(ack), ack_bits })
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let (head, rest) = self.0.split_first_chunk::<N>()?;
+        self.0 = rest;
+        Some(*head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take::<1>().map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take().map(u16::from_be_bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take().map(u32::from_be_bytes)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.take().map(u64::from_be_bytes)
+    }
+}
+
+/// wrap-around를 고려한 u16 packet 순서 번호 비교
+fn packet_seq_greater_than(seq: u16, other: u16) -> bool {
+    seq != other && seq.wrapping_sub(other) < u16::MAX / 2
+}
+
+struct SentMessage {
+    channel: ChannelKind,
+    message_seq: u32,
+    payload: Vec<u8>,
+    sent_at: Instant,
+}
+
+/// handshake가 끝난 UDP 연결 하나의 상태 (서버, 클라이언트 공용)
+/// 소켓은 가지고 있지 않고, 보낼 datagram을 만들어 주거나 받은 packet에서 전달할 payload를 꺼내준다.
+pub struct UdpConnection {
+    local_seq: u16,
+    remote_seq: Option<u16>,
+    remote_ack_bits: u32,
+    /// ack를 기다리는 reliable 메시지 (packet 순서 번호 기준)
+    sent: HashMap<u16, SentMessage>,
+    ordered_next: u32,
+    ordered_buffer: HashMap<u32, Vec<u8>>,
+    unordered_received: HashSet<u32>,
+    ack_pending: bool,
+    last_sent: Instant,
+    last_received: Instant,
+}
+
+impl UdpConnection {
+    pub fn new(now: Instant) -> Self {
+        UdpConnection {
+            local_seq: 0,
+            remote_seq: None,
+            remote_ack_bits: 0,
+            sent: HashMap::new(),
+            ordered_next: 0,
+            ordered_buffer: HashMap::new(),
+            unordered_received: HashSet::new(),
+            ack_pending: false,
+            last_sent: now,
+            last_received: now,
+        }
+    }
+
+    fn next_header(&mut self, now: Instant) -> AckHeader {
+        let header = AckHeader { seq: self.local_seq, ack: self.remote_seq, ack_bits: self.remote_ack_bits };
+        self.local_seq = self.local_seq.wrapping_add(1);
+        self.ack_pending = false;
+        self.last_sent = now;
+        header
+    }
+
+    /// 메시지 하나를 datagram으로 만든다. reliable 채널이면 ack를 받을 때까지 보관한다.
+    pub fn send(&mut self, channel: ChannelKind, message_seq: u32, payload: Vec<u8>, now: Instant) -> Vec<u8> {
+        let header = self.next_header(now);
+
+        if channel != ChannelKind::UnreliableSequenced {
+            self.sent.insert(header.seq, SentMessage { channel, message_seq, payload: payload.clone(), sent_at: now });
+        }
+
+        UdpPacket::Data { header, channel, message_seq, payload }.encode()
+    }
+
+    /// 받은 packet의 ack를 처리하고, 애플리케이션에 전달할 payload를 순서대로 돌려준다.
+    pub fn receive(&mut self, packet: UdpPacket, now: Instant) -> Vec<Vec<u8>> {
+        let (header, data) = match packet {
+            UdpPacket::Data { header, channel, message_seq, payload } => (header, Some((channel, message_seq, payload))),
+            UdpPacket::KeepAlive { header } => (header, None),
+            _ => return Vec::new(),
+        };
+
+        self.last_received = now;
+        self.process_acks(&header);
+
+        // 보관 범위를 넘는 메시지는 ack하지 않아서 보내는 쪽이 다시 보내도록 한다.
+        if let Some((ChannelKind::ReliableOrdered, message_seq, _)) = &data {
+            if self.beyond_reorder_window(*message_seq) {
+                return Vec::new();
+            }
+        }
+        self.record_received(header.seq);
+
+        match data {
+            Some((channel, message_seq, payload)) => {
+                self.ack_pending = true;
+                self.deliver(channel, message_seq, payload)
+            },
+            None => Vec::new(),
+        }
+    }
+
+    /// 주기적으로 호출하여 ack를 받지 못한 reliable 메시지 재전송, ack / keep alive packet을 만든다.
+    pub fn poll(&mut self, now: Instant) -> Vec<Vec<u8>> {
+        let expired: Vec<u16> = self.sent.iter()
+            .filter(|(_, sent)| now.duration_since(sent.sent_at) >= RESEND_TIMEOUT)
+            .map(|(seq, _)| *seq)
+            .collect();
+
+        let mut datagrams = Vec::new();
+        for seq in expired {
+            if let Some(sent) = self.sent.remove(&seq) {
+                datagrams.push(self.send(sent.channel, sent.message_seq, sent.payload, now));
+            }
+        }
+
+        let since_sent = now.duration_since(self.last_sent);
+        if datagrams.is_empty() && ((self.ack_pending && since_sent >= ACK_INTERVAL) || since_sent >= KEEP_ALIVE_INTERVAL) {
+            let header = self.next_header(now);
+            datagrams.push(UdpPacket::KeepAlive { header }.encode());
+        }
+
+        datagrams
+    }
+
+    pub fn is_timed_out(&self, now: Instant) -> bool {
+        now.duration_since(self.last_received) >= CONNECTION_TIMEOUT
+    }
+
+    fn record_received(&mut self, seq: u16) {
+        match self.remote_seq {
+            None => {
+                self.remote_seq = Some(seq);
+                self.remote_ack_bits = 0;
+            },
+            Some(latest) if packet_seq_greater_than(seq, latest) => {
+                let diff = seq.wrapping_sub(latest) as u32;
+                self.remote_ack_bits = if diff > 32 {
+                    0
+                } else {
+                    self.remote_ack_bits.checked_shl(diff).unwrap_or(0) | (1 << (diff - 1))
+                };
+                self.remote_seq = Some(seq);
+            },
+            Some(latest) => {
+                let diff = latest.wrapping_sub(seq) as u32;
+                if (1..=32).contains(&diff) {
+                    self.remote_ack_bits |= 1 << (diff - 1);
+                }
+            },
+        }
+    }
+
+    fn process_acks(&mut self, header: &AckHeader) {
+        let Some(ack) = header.ack else {
+            return;
+        };
+
+        self.sent.remove(&ack);
+        for i in 0..32u16 {
+            if header.ack_bits & (1 << i) != 0 {
+                self.sent.remove(&ack.wrapping_sub(i + 1));
+            }
+        }
+    }
+
+    fn beyond_reorder_window(&self, message_seq: u32) -> bool {
+        super::sequence_greater_than(message_seq, self.ordered_next) && message_seq.wrapping_sub(self.ordered_next) >= REORDER_WINDOW
+    }
+
+    fn deliver(&mut self, channel: ChannelKind, message_seq: u32, payload: Vec<u8>) -> Vec<Vec<u8>> {
+        match channel {
+            // 오래된 메시지 판단은 애플리케이션의 `SequenceFilter`에서 한다.
+            ChannelKind::UnreliableSequenced => vec![payload],
+            ChannelKind::ReliableUnordered => {
+                if !self.unordered_received.insert(message_seq) {
+                    return Vec::new();
+                }
+                if self.unordered_received.len() > 4096 {
+                    self.unordered_received.retain(|seq| message_seq.wrapping_sub(*seq) < 2048);
+                }
+                vec![payload]
+            },
+            ChannelKind::ReliableOrdered => {
+                if message_seq != self.ordered_next {
+                    // 앞선 메시지가 아직 도착하지 않았으면 보관, 이미 전달한 메시지면 버린다.
+                    if super::sequence_greater_than(message_seq, self.ordered_next) {
+                        self.ordered_buffer.insert(message_seq, payload);
+                    }
+                    return Vec::new();
+                }
+
+                let mut delivered = vec![payload];
+                self.ordered_next = self.ordered_next.wrapping_add(1);
+                while let Some(payload) = self.ordered_buffer.remove(&self.ordered_next) {
+                    delivered.push(payload);
+                    self.ordered_next = self.ordered_next.wrapping_add(1);
+                }
+                delivered
+            },
+        }
+    }
+}
@@ -1,6 +1,6 @@
-use std::env;
+use std::{collections::HashMap, env, sync::Arc};
 
-use crate::{client::run_client, server::run_server};
+use crate::{client::run_client, common::{compression::CompressionSettings, TransportKind}, server::{run_determinism_check, run_replay, run_server, Authenticator, ConnectionLimits, GuestAuthenticator, StaticTokenAuthenticator, TlsConfig}};
 
 mod client;
 mod server;
@@ -17,15 +17,97 @@ fn main() {
 
     println!("args: {}", args[1]);
 
+    // 두번째 인자부터 전송 방식을 고른다. (ws, udp, unix)
+    // 서버는 나열한 전송 방식을 모두 열고, 클라이언트는 첫번째 전송 방식으로 접속한다.
+    let (positional, options) = parse_options(&args[2..]);
+    let mut transports: Vec<TransportKind> = positional.iter().map(|arg| TransportKind::from(arg.as_str())).collect();
+    if transports.is_empty() {
+        transports.push(TransportKind::WebSocket);
+    }
+
     match args[1].as_str() {
         "server" => {
-            run_server();
+            // --cert, --key를 모두 지정하면 WebSocket listener가 wss://로 동작한다.
+            let tls = match (options.get("cert"), options.get("key")) {
+                (Some(cert_path), Some(key_path)) => Some(TlsConfig { cert_path: cert_path.clone(), key_path: key_path.clone() }),
+                _ => None,
+            };
+            // --tokens: `토큰 계정id` 목록 파일, 지정하지 않으면 모든 접속을 guest로 허용한다.
+            let authenticator: Arc<dyn Authenticator> = match options.get("tokens") {
+                Some(tokens_path) => match StaticTokenAuthenticator::from_file(tokens_path) {
+                    Ok(authenticator) => Arc::new(authenticator),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        return;
+                    },
+                },
+                None => Arc::new(GuestAuthenticator),
+            };
+            // --max-players, --max-per-ip: 최대 플레이어 수, IP별 최대 연결 수
+            let mut limits = ConnectionLimits::default();
+            if let Some(max_players) = options.get("max-players").and_then(|value| value.parse().ok()) {
+                limits.max_players = max_players;
+            }
+            if let Some(max_per_ip) = options.get("max-per-ip").and_then(|value| value.parse().ok()) {
+                limits.max_connections_per_ip = max_per_ip;
+            }
+            // --compression off: 압축 사용 안 함, --compression-threshold: 이 크기(바이트) 이상인 frame만 압축
+            let mut compression = CompressionSettings::default();
+            if options.get("compression").is_some_and(|value| value == "off") {
+                compression.enabled = false;
+            }
+            if let Some(threshold) = options.get("compression-threshold").and_then(|value| value.parse().ok()) {
+                compression.threshold = threshold;
+            }
+            // --seed: 결정적 모드, 받은 메시지를 다음 tick에 클라이언트 id 순서로 처리하고 난수를 seed로 초기화한다.
+            let seed = options.get("seed").and_then(|value| value.parse().ok());
+            // --record: 처리한 클라이언트 메시지를 tick과 함께 파일에 기록한다. (`replay`로 재생)
+            run_server(transports, tls, authenticator, limits, compression, seed, options.get("record").cloned());
         },
         "client" => {
-            run_client();
+            // --url: WebSocket 접속 주소 (wss:// 가능) 또는 UDP 서버 주소 (host:port), --ca: 신뢰할 CA 인증서 PEM 파일, --token: 인증 토큰
+            // --compression off: 압축된 frame을 받지 않음
+            let compression = options.get("compression").is_none_or(|value| value != "off");
+            run_client(transports[0], options.get("url").cloned(), options.get("ca").cloned(), options.get("token").cloned(), compression);
+        },
+        "determinism" => {
+            // 소켓 없이 같은 입력 기록으로 시뮬레이션을 여러 번 돌려서 결과가 같은지 확인한다. (--seed, 기본값 0)
+            let seed = options.get("seed").and_then(|value| value.parse().ok()).unwrap_or(0);
+            if let Err(reason) = run_determinism_check(seed) {
+                eprintln!("[determinism] FAILED: {}", reason);
+                std::process::exit(1);
+            }
+        },
+        "replay" => {
+            // 기록 파일로 소켓 없이 월드를 다시 만든다. --tick: 해당 tick까지 진행해서 상태를 출력하고 종료, 없으면 한 tick씩 진행
+            let Some(path) = positional.first() else {
+                println!("replay file is required, ex) cargo run replay session.log");
+                return;
+            };
+            run_replay(path, options.get("tick").and_then(|value| value.parse().ok()));
         },
         _ => {
             println!("fault params!");
         }
     }
 }
+
+/// `--name value` 형태의 옵션과 나머지 인자를 나눈다.
+fn parse_options(args: &[String]) -> (Vec<String>, HashMap<String, String>) {
+    let mut positional = Vec::new();
+    let mut options = HashMap::new();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.strip_prefix("--") {
+            Some(name) => {
+                if let Some(value) = iter.next() {
+                    options.insert(name.to_string(), value.clone());
+                }
+            },
+            None => positional.push(arg.clone()),
+        }
+    }
+
+    (positional, options)
+}
@@ -1,63 +1,135 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc, time::Instant};
 
-use bevy::prelude::*;
-use futures_util::{future, stream::SplitSink, SinkExt, StreamExt, TryStreamExt};
-use tokio::{net::TcpStream, sync::mpsc::{Receiver, Sender}};
-use tokio_tungstenite::{tungstenite::Message, WebSocketStream};
+use bevy::{ecs::system::SystemParam, prelude::*};
+use futures_util::{future, stream::SplitSink, SinkExt, StreamExt};
+use tokio::{io::{AsyncRead, AsyncWrite}, sync::mpsc::{Receiver, Sender}};
+use tokio_tungstenite::{tungstenite::{self, handshake::server::{ErrorResponse, Request, Response}, http::{header::AUTHORIZATION, HeaderValue, StatusCode}, protocol::{frame::coding::CloseCode, CloseFrame, WebSocketConfig}, Message}, WebSocketStream};
+use tokio_rustls::TlsAcceptor;
 use uuid::Uuid;
 
-use crate::common::ServerMessage;
-
-pub fn run_server() {
+use crate::common::{compression::{self, CompressionSettings, CompressionStats, COMPRESSION_HEADER, DEFLATE}, ChannelKind, ChannelSequencer, ClientMessage, MatchInfo, Outgoing, Packet, PlayerInput, PlayerJoined, ServerMessage, TransportKind, PLAYER_RADIUS, TICK_RATE, UNIX_SOCKET_PATH};
+use admission::{Admission, Violation};
+use aggregate::{AggregatePlugin, OutboundBuffer};
+use auth::AccountId;
+use clock::{ClockPlugin, ServerTick};
+use deterministic::{DeterministicPlugin, PendingClientMessages, SimulationRng};
+use event::ClientEvents;
+use outbound::{outbound_queue, OutboundError, OutboundReceiver, OutboundSender, OUTBOUND_QUEUE_CAPACITY};
+use history::LagCompensationPlugin;
+use input::{InputBuffer, InputPlugin};
+use interest::{ClientVisibility, InterestPlugin, NetworkOwner, Replicated, Team};
+use movement::{MovementPlugin, Velocity};
+use physics::{Collider, PhysicsPlugin};
+use replay::{MessageRecorder, RecordPlugin};
+use priority::{BandwidthSettings, PriorityPlugin, ReplicationPriority, ReplicationQueue};
+
+pub use admission::ConnectionLimits;
+pub use auth::{Authenticator, GuestAuthenticator, StaticTokenAuthenticator};
+pub use deterministic::run_determinism_check;
+pub use replay::run_replay;
+pub use event::{SendTarget, ServerEvent, ServerEventAppExt};
+pub use resource::ReplicateResourceExt;
+pub use tls::TlsConfig;
+
+mod admission;
+mod aggregate;
+mod auth;
+mod clock;
+mod deterministic;
+mod event;
+mod history;
+mod input;
+mod interest;
+mod movement;
+mod outbound;
+mod physics;
+mod priority;
+mod replay;
+mod resource;
+mod tls;
+mod udp;
+
+/// 플레이어가 처음 생성되는 영역 (원점 기준 반 크기), 위치는 `SimulationRng`로 정한다.
+const SPAWN_RANGE: f32 = 300.0;
+/// 팀 수, 접속한 순서대로 번갈아 배정한다.
+const TEAM_COUNT: u32 = 2;
+/// 플레이어 엔티티의 기본 복제 우선순위, 다른 복제 엔티티보다 먼저 보낸다.
+const PLAYER_REPLICATION_PRIORITY: f32 = 2.0;
+
+/// `seed`가 있으면 결정적 모드로 동작한다. (`DeterministicPlugin` 참고)
+/// `record`가 있으면 처리한 클라이언트 메시지를 파일에 기록한다. (`RecordPlugin` 참고)
+pub fn run_server(transports: Vec<TransportKind>, tls: Option<TlsConfig>, authenticator: Arc<dyn Authenticator>, limits: ConnectionLimits, compression: CompressionSettings, seed: Option<u64>, record: Option<String>) {
     let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    // tls 설정이 있으면 WebSocket listener는 wss://로 동작한다.
+    let tls_acceptor = match tls.map(|tls| tls.load_acceptor()).transpose() {
+        Ok(tls_acceptor) => tls_acceptor,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        },
+    };
     
-    App::new()
-        .add_plugins(MinimalPlugins)
-        .insert_resource(TokioRuntime(runtime.handle().clone()))
+    let mut app = server_app(seed);
+    if let Some(path) = record {
+        app.add_plugins(RecordPlugin { path });
+    }
+    app.insert_resource(TokioRuntime(runtime.handle().clone()))
+        .insert_resource(ServerTransports(transports))
+        .insert_resource(WebSocketTls(tls_acceptor))
+        .insert_resource(WebSocketCompression { settings: compression, stats: Arc::new(CompressionStats::default()) })
+        .insert_resource(ServerAuthenticator(authenticator))
+        .insert_resource(ServerAdmission(Admission::new(limits)))
+        .add_systems(Startup, setup_server)
+        .run();
+}
+
+/// listener를 제외한 서버 App (시뮬레이션, 복제, 클라이언트 메시지 처리)
+/// 클라이언트 메시지는 `WebSocketAcceptEvent`로 받으며, `run_server`는 `setup_server`에서 listener와 함께 넣어준다.
+fn server_app(seed: Option<u64>) -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .configure_sets(PostUpdate, (ReplicationSet::Interest, ReplicationSet::Send, ReplicationSet::Flush).chain())
+        .configure_sets(FixedUpdate, (SimulationSet::Receive, SimulationSet::Input, SimulationSet::Movement, SimulationSet::Collision, SimulationSet::Record).chain())
+        .add_plugins((InterestPlugin, PriorityPlugin, AggregatePlugin, ClockPlugin, InputPlugin, MovementPlugin, PhysicsPlugin, LagCompensationPlugin))
+        .add_plugins(DeterministicPlugin { seed })
         .insert_resource(UuidMap(HashMap::new()))
-        .add_event::<ClientMoveEvent>()
+        .insert_resource(MatchInfo { map_name: "arena".to_string(), elapsed_secs: 0 })
+        .replicate_resource::<MatchInfo>()
+        .add_server_event::<PlayerJoined>()
         .add_event::<SinkEvent>()
-        .add_systems(Startup, setup_server)
         .add_systems(Update, (
             clinet_event_receive_system,
-            client_move_event_system
-        ))
-        .run();
+            match_timer_system,
+        ));
+    app
 }
 
 struct ClientConnectInfo {
     uuid: Uuid,
-    sender: Sender<Message>,
+    account: AccountId,
+    sender: OutboundSender,
+    transport: TransportKind,
 }
 
 impl ClientConnectInfo {
-    pub fn new(uuid: Uuid, sender: Sender<Message>) -> Self {
-        ClientConnectInfo { uuid, sender }
+    pub fn new(uuid: Uuid, account: AccountId, sender: OutboundSender, transport: TransportKind) -> Self {
+        ClientConnectInfo { uuid, account, sender, transport }
     }
 }
 
 // enum
 enum ClientEventMessage {
     Connect(ClientConnectInfo), // 연결
-    Move(MoveDirection, Uuid),
-}
-
-enum MoveDirection {
-    Up,
-    Down,
-    Left,
-    Right,
-    None,
+    Event(ClientMessage, Uuid), // `add_client_event`로 등록된 event
+    Disconnect(Uuid), // 연결 종료
 }
 
-impl From<&str> for MoveDirection {
-    fn from(value: &str) -> Self {
-        match value {
-            "up" => MoveDirection::Up,
-            "down" => MoveDirection::Down,
-            "left" => MoveDirection::Left,
-            "right" => MoveDirection::Right,
-            _ => MoveDirection::None,
+impl ClientEventMessage {
+    fn uuid(&self) -> Uuid {
+        match self {
+            ClientEventMessage::Connect(info) => info.uuid,
+            ClientEventMessage::Event(_, uuid) | ClientEventMessage::Disconnect(uuid) => *uuid,
         }
     }
 }
@@ -67,13 +139,37 @@ impl From<&str> for MoveDirection {
 struct Client(Uuid);
 
 #[derive(Component)]
-struct ClientSender(Sender<Message>);
+struct ClientSender(OutboundSender);
 
 // ----------------- resource
 
 #[derive(Resource)]
 struct TokioRuntime(tokio::runtime::Handle);
 
+/// 클라이언트 접속을 받을 전송 방식 목록
+/// 모든 listener는 같은 `WebSocketAcceptEvent` 채널로 메시지를 보내고 하나의 `UuidMap`을 공유한다.
+#[derive(Resource)]
+struct ServerTransports(Vec<TransportKind>);
+
+/// WebSocket(TCP) listener에서 사용할 TLS acceptor, `None`이면 ws://
+#[derive(Resource)]
+struct WebSocketTls(Option<TlsAcceptor>);
+
+/// WebSocket 메시지 압축 설정과 모든 연결의 압축 전후 바이트 수
+#[derive(Resource, Clone)]
+struct WebSocketCompression {
+    settings: CompressionSettings,
+    stats: Arc<CompressionStats>,
+}
+
+/// 모든 listener가 공유하는 인증 방식
+#[derive(Resource)]
+struct ServerAuthenticator(Arc<dyn Authenticator>);
+
+/// 모든 listener가 공유하는 접속 제한 (최대 플레이어 수, IP별 연결 수)
+#[derive(Resource)]
+struct ServerAdmission(Admission);
+
 #[derive(Resource)]
 struct WebSocketAcceptEvent(Receiver<ClientEventMessage>);  // Websocket으로 받아온 데이터를 처리해야함..
 
@@ -83,20 +179,115 @@ struct WebSocketSinkEvent(Sender<Message>);
 #[derive(Resource)]
 struct UuidMap(pub HashMap<Uuid, Entity>);
 
-// ----------------- event
-#[derive(Event)]
-struct ClientMoveEvent{
-    uuid: Uuid,
-    move_direction: MoveDirection, 
+// ----------------- system set
+
+/// 복제 단계: 관심 영역 계산 후 대역폭 예산에 맞춰 보낼 메시지를 고르고, 클라이언트마다 모아서 한 번에 전송
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+enum ReplicationSet {
+    Interest,
+    Send,
+    Flush,
+}
+
+/// 고정 tick 단계: (결정적 모드에서는 모아둔 클라이언트 메시지를 처리하고) 입력 버퍼에서 이번 tick 입력을 꺼낸 뒤
+/// 이동을 적분하고, 충돌을 푼 결과를 지연 보상용으로 기록
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+enum SimulationSet {
+    Receive,
+    Input,
+    Movement,
+    Collision,
+    Record,
+}
+
+// ----------------- system param
+
+/// 클라이언트 메시지 처리
+/// 받은 즉시 처리하는 `clinet_event_receive_system`과 결정적 모드에서 다음 고정 tick에 모아서 처리하는 시스템이 같이 사용한다.
+#[derive(SystemParam)]
+struct ClientMessageHandler<'w, 's> {
+    commands: Commands<'w, 's>,
+    client_events: Res<'w, ClientEvents>,
+    player_joined: EventWriter<'w, ServerEvent<PlayerJoined>>,
+    uuid_map: ResMut<'w, UuidMap>,
+    bandwidth_settings: Res<'w, BandwidthSettings>,
+    rng: ResMut<'w, SimulationRng>,
 }
 
+impl ClientMessageHandler<'_, '_> {
+    /// ## Connect: 
+    /// Component
+    /// - Client(Uuid): 클라이언트 식별
+    /// - AccountId: 인증된 계정 id
+    /// - ClientSink(sink): 연결된 클라이언트에게 데이터 전송
+    /// - Transform: 위치 정보, `SPAWN_RANGE` 안의 임의 위치
+    /// - NetworkOwner, Team: 자신이 소유한 엔티티, 접속 순서대로 번갈아 정한 팀
+    /// - ReplicationPriority: `PLAYER_REPLICATION_PRIORITY`
+    /// 
+    /// todo here ...
+    fn handle(&mut self, msg: ClientEventMessage) {
+        match msg {
+            ClientEventMessage::Connect(info) => {
+                println!("client connect success!!, it will make client entity");
+                // 다른 클라이언트들에게 새 플레이어를 알려준다.
+                self.player_joined.write(ServerEvent { target: SendTarget::AllExcept(info.uuid), channel: ChannelKind::ReliableOrdered, event: PlayerJoined { account: info.account.0.clone() } });
+
+                // Client entity 생성: Transform Componenet를 가지고 있어야함
+                // 이 후 입력(`PlayerInput`)이 오면 고정 tick마다 Transform 위치를 변경시킨다.
+                let spawn = Vec3::new(self.rng.range(-SPAWN_RANGE, SPAWN_RANGE), self.rng.range(-SPAWN_RANGE, SPAWN_RANGE), 0.0);
+                let team = Team(self.uuid_map.0.len() as u32 % TEAM_COUNT);
+                let entity = self.commands.spawn((
+                    Client(info.uuid),
+                    info.account,
+                    ClientSender(info.sender),
+                    info.transport,
+                    Transform::from_translation(spawn),
+                    PlayerInput::default(),
+                    InputBuffer::default(),
+                    Velocity::default(),
+                    Collider::Circle { radius: PLAYER_RADIUS },
+                    Replicated,
+                    ClientVisibility::default(),
+                    self.bandwidth_settings.budget(),
+                    ReplicationQueue::default(),
+                    ChannelSequencer::default(),
+                )).id();
+
+                // 클라이언트가 자신의 엔티티를 구분할 수 있도록 알려준다.
+                let mut buffer = OutboundBuffer::default();
+                buffer.push(ServerMessage::Welcome { entity: entity.to_bits() });
+                self.commands.entity(entity).insert((buffer, NetworkOwner(entity), team, ReplicationPriority(PLAYER_REPLICATION_PRIORITY)));
+
+                // uuid - entity 추가 
+                self.uuid_map.0.insert(info.uuid, entity);
+            },
+            ClientEventMessage::Event(client_msg, uuid) => {
+                // 연결이 끊어지는 중이라 엔티티가 이미 없으면 무시한다.
+                let Some(&entity) = self.uuid_map.0.get(&uuid) else {
+                    return;
+                };
+                self.client_events.apply(&mut self.commands, entity, uuid, client_msg);
+            },
+            ClientEventMessage::Disconnect(uuid) => {
+                println!("client disconnected, uuid: {}", uuid);
+                // 엔티티가 사라지면 관심 영역 시스템이 다른 클라이언트들에게 despawn 메시지를 보내준다.
+                if let Some(entity) = self.uuid_map.0.remove(&uuid) {
+                    self.commands.entity(entity).despawn();
+                }
+            },
+        }
+    }
+}
+
+// ----------------- event
 #[derive(Event)]
 struct SinkEvent;
 
 // ----------------- system
-fn setup_server(mut commands: Commands, tokio_runtime: Res<TokioRuntime>) {
+fn setup_server(mut commands: Commands, tokio_runtime: Res<TokioRuntime>, transports: Res<ServerTransports>, tls: Res<WebSocketTls>, compression: Res<WebSocketCompression>, authenticator: Res<ServerAuthenticator>, admission: Res<ServerAdmission>) {
     // websocket server Message channel
-    let (stream_tx, stream_rx) = tokio::sync::mpsc::channel::<ClientEventMessage>(10);
+    // 연결마다 reader task가 받은 순서대로 보내고, 가득 차면 reader task가 기다린다.
+    let (stream_tx, stream_rx) = tokio::sync::mpsc::channel::<ClientEventMessage>(1024);
     let (sink_tx, sink_rx) = tokio::sync::mpsc::channel::<Message>(10);
 
     // resource 추가 
@@ -104,107 +295,188 @@ fn setup_server(mut commands: Commands, tokio_runtime: Res<TokioRuntime>) {
     commands.insert_resource(WebSocketSinkEvent(sink_tx));
 
     let handle = tokio_runtime.0.clone();
-    handle.spawn(async move {
-        handle_websocket(stream_tx).await;
-        println!("finish the websocker waiting...");
-    });
+    for transport in transports.0.iter() {
+        let stream_tx = stream_tx.clone();
+        let authenticator = authenticator.0.clone();
+        let admission = admission.0.clone();
+        let compression = compression.clone();
+        match transport {
+            TransportKind::WebSocket => {
+                let tls_acceptor = tls.0.clone();
+                handle.spawn(async move {
+                    handle_websocket(stream_tx, tls_acceptor, compression, authenticator, admission).await;
+                    println!("finish the websocker waiting...");
+                });
+            },
+            TransportKind::Udp => {
+                handle.spawn(async move {
+                    udp::handle_udp(stream_tx, authenticator, admission).await;
+                    println!("finish the udp waiting...");
+                });
+            },
+            TransportKind::UnixSocket => {
+                handle.spawn(async move {
+                    handle_unix_websocket(stream_tx, compression, authenticator, admission).await;
+                    println!("finish the unix socket waiting...");
+                });
+            },
+        }
+    }
 }
 
 /// ClinetEventMessage
-/// ## Connect: 
-/// Component
-/// - Client(Uuid): 클라이언트 식별
-/// - ClientSink(sink): 연결된 클라이언트에게 데이터 전송
-/// - Transform: 위치 정보 
-/// 
-/// todo here ...
-fn clinet_event_receive_system(mut commands: Commands, mut recv: ResMut<WebSocketAcceptEvent>, mut client_move_event: EventWriter<ClientMoveEvent>, mut uuid_map: ResMut<UuidMap>) {
-    match recv.0.try_recv() {
-        Ok(msg) => {
-            match msg {
-                ClientEventMessage::Connect(info) => {
-                    println!("client connect success!!, it will make client entity");
-                    // Client entity 생성: Transform Componenet를 가지고 있어야함
-                    // 이 후 방향 메시지가 왔을 때, 해당 Transform 위치를 변경시켜줘야함.
-                    let entity = commands.spawn((
-                        Client(info.uuid),
-                        ClientSender(info.sender),
-                        Transform::from_translation(Vec3::new(0.0, 0.0, 0.0)),
-                    ));
-
-                    // uuid - entity 추가 
-                    uuid_map.0.insert(info.uuid, entity.id());
-                },
-                ClientEventMessage::Move(move_event, uuid) => {
-                    // todo here ...
-                    // client move event 발생!
-                    // event를 발생시켜서 위치를 변경시키는 작업을 하면 좋을 것 같음. 
-                    match move_event {
-                        MoveDirection::Up => println!("up"),
-                        MoveDirection::Down => println!("down"),
-                        MoveDirection::Left => println!("left"),
-                        MoveDirection::Right => println!("right"),
-                        MoveDirection::None => print!("none"),
-                    }
+/// 결정적 모드에서는 처리하지 않고 다음 고정 tick까지 모아둔다. (`PendingClientMessages`)
+/// 바로 처리한 메시지는 다음 고정 tick부터 반영되므로 다음 tick으로 기록한다.
+fn clinet_event_receive_system(
+    tick: Res<ServerTick>,
+    mut recv: ResMut<WebSocketAcceptEvent>,
+    mut pending: Option<ResMut<PendingClientMessages>>,
+    mut recorder: Option<ResMut<MessageRecorder>>,
+    mut handler: ClientMessageHandler,
+) {
+    // 한 프레임에 쌓인 메시지를 모두 처리한다.
+    while let Ok(msg) = recv.0.try_recv() {
+        match pending.as_mut() {
+            Some(pending) => pending.push(msg),
+            None => {
+                if let Some(recorder) = recorder.as_mut() {
+                    recorder.record(tick.0 + 1, &msg);
+                }
+                handler.handle(msg);
+            },
+        }
+    }
+}
 
-                    // client move event write!
-                    client_move_event.write(ClientMoveEvent{ uuid: uuid,move_direction: move_event});
-                },
-            }
-        },
-        Err(_) => {
+/// 경기 시간은 초 단위로만 복제하므로 값이 바뀔 때만 변경으로 표시한다.
+/// 결정적 모드에서도 같은 값이 되도록 프레임 시간 대신 서버 tick으로 계산한다.
+fn match_timer_system(tick: Res<ServerTick>, mut match_info: ResMut<MatchInfo>) {
+    let elapsed_secs = (tick.0 as f64 / TICK_RATE) as u32;
+    if match_info.elapsed_secs != elapsed_secs {
+        match_info.elapsed_secs = elapsed_secs;
+    }
+}
 
-        },
+/// ServerMessage에 채널과 순서 번호를 붙인다. 직렬화는 writer task에서 한다. (`encode_packet`, `encode_batch`)
+/// 채널은 보내는 쪽에서 `OutboundBuffer::push_on`, `ServerEvent::channel`로 고르며, 고르지 않으면 `ServerMessage::default_channel`을 사용한다.
+fn encode_message(sequencer: &mut ChannelSequencer, channel: ChannelKind, server_msg: ServerMessage) -> Outgoing<ServerMessage> {
+    let seq = sequencer.next(channel);
+    Outgoing { channel, seq, key: server_msg.supersede_key(), message: server_msg }
+}
+
+/// packet 하나를 json 문자열로 직렬화한다. (`Frame::Single`)
+fn encode_packet(outgoing: &Outgoing<ServerMessage>) -> String {
+    let packet = Packet { channel: outgoing.channel, seq: outgoing.seq, message: &outgoing.message };
+    serde_json::to_string(&packet).unwrap()
+}
+
+/// packet들을 json 배열 하나로 묶는다. (`Frame::Batch`)
+fn encode_batch(pending: &[Outgoing<ServerMessage>]) -> String {
+    let packets: Vec<Packet<&ServerMessage>> = pending.iter()
+        .map(|outgoing| Packet { channel: outgoing.channel, seq: outgoing.seq, message: &outgoing.message })
+        .collect();
+    serde_json::to_string(&packets).unwrap()
+}
+
+/// 압축한 결과가 더 작을 때만 binary 메시지로 보낸다.
+fn compress_frame(frame: String, threshold: Option<usize>, stats: &CompressionStats) -> Message {
+    if threshold.is_some_and(|threshold| frame.len() >= threshold) {
+        let compressed = compression::compress(frame.as_bytes());
+        if compressed.len() < frame.len() {
+            stats.record(frame.len(), compressed.len(), true);
+            return Message::binary(compressed);
+        }
     }
+
+    stats.record(frame.len(), frame.len(), false);
+    Message::text(frame)
 }
 
-fn client_move_event_system(mut client_move_event: EventReader<ClientMoveEvent>, mut query: Query<(&mut Transform, &ClientSender, &Client)>, tokio_handle: Res<TokioRuntime>, uuid_map: Res<UuidMap>) {
-    
-    for event in client_move_event.read() {
-        // 1. client Entity의 transform component 값을 변경시킨다.
-        // 2. 변경된 위치 좌표를 ClientSink Component의 sink 값을 통해서 보내준다.
-        let entity = uuid_map.0.get(&event.uuid).unwrap();
-        let (mut transform, sender, _) = query.get_mut(*entity).unwrap();
-        
-        match event.move_direction {
-            MoveDirection::Up => transform.translation.y += 10.0,
-            MoveDirection::Down => transform.translation.y -= 10.0,
-            MoveDirection::Left => transform.translation.x -= 10.0,
-            MoveDirection::Right => transform.translation.x += 10.0,
-            MoveDirection::None => {},
+/// 대역폭 계산에 사용하는 직렬화된 메시지 크기
+fn encoded_len(server_msg: &ServerMessage) -> usize {
+    let packet = Packet { channel: server_msg.default_channel(), seq: 0, message: server_msg };
+    serde_json::to_vec(&packet).map_or(0, |bytes| bytes.len())
+}
+
+/// 클라이언트에게 보낼 메시지들을 송신 대기열에 넣는다. task를 만들지 않고 바로 넣으므로 넣은 순서가 그대로 유지된다.
+/// 대기열이 넘치면 writer task가 연결을 끊는다. (`outbound` 참고)
+fn send_messages(sender: &OutboundSender, msgs: Vec<Outgoing<ServerMessage>>) {
+    for msg in msgs {
+        match sender.push(msg) {
+            Ok(_) => {},
+            Err(OutboundError::Overflow) => {
+                eprintln!("send queue overflow, client will be disconnected");
+                return;
+            },
+            Err(OutboundError::Closed) => return,
         }
-        println!("move event occur!");
-
-        let cloned_transform = transform.translation.clone();
-        let cloned_tx = sender.0.clone();
-
-        tokio_handle.0.spawn(async move {
-            let server_msg = ServerMessage::PlayerUpdate { translation: cloned_transform };
-            let json_str = serde_json::to_string(&server_msg).unwrap();
-            println!("json: {}", json_str);
-            let msg = Message::text(json_str);
-            match cloned_tx.send(msg).await {
-                Ok(_) => {},
-                Err(e) => {
-                    eprintln!("error: {}", e);
-                },
-            }
-        });
-    }    
+    }
 }
 
 // handler 
 /// StartUp 시에 클라이언트의 접속을 처리해주는 함수 
 /// 성공적으로 연결이되면 `stream`을 새로운 task로 넘겨준다. 새로 생성된 task에서는 `handle_accept`를 호출해서 처리해준다.
-async fn handle_websocket(tx: Sender<ClientEventMessage>) {
+/// `tls_acceptor`가 있으면 TLS handshake를 먼저 진행한다. (wss://)
+/// IP별 연결 수를 넘은 연결은 task를 만들지 않고 바로 끊는다.
+async fn handle_websocket(tx: Sender<ClientEventMessage>, tls_acceptor: Option<TlsAcceptor>, compression: WebSocketCompression, authenticator: Arc<dyn Authenticator>, admission: Admission) {
     let tcp_listener = tokio::net::TcpListener::bind("0.0.0.0:9003").await.unwrap();
 
     loop {
         let cloned_tx = tx.clone();
         match tcp_listener.accept().await {
-            Ok((stream, _)) => {
+            Ok((stream, addr)) => {
+                let permit = match admission.admit_connection(addr.ip()) {
+                    Ok(permit) => permit,
+                    Err(reason) => {
+                        println!("connection refused, addr: {}, reason: {}", addr, reason);
+                        continue;
+                    },
+                };
+
+                let tls_acceptor = tls_acceptor.clone();
+                let compression = compression.clone();
+                let authenticator = authenticator.clone();
+                let admission = admission.clone();
                 tokio::spawn(async move { 
-                    handle_accept(stream, cloned_tx).await;
+                    // 연결이 끝날 때까지 IP별 연결 수에 포함된다.
+                    let _permit = permit;
+                    // TLS, WebSocket handshake를 합쳐서 `handshake_timeout` 안에 끝나야 한다.
+                    let deadline = tokio::time::Instant::now() + admission.handshake_timeout();
+                    match tls_acceptor {
+                        Some(tls_acceptor) => match tokio::time::timeout_at(deadline, tls_acceptor.accept(stream)).await {
+                            Ok(Ok(tls_stream)) => handle_accept(tls_stream, TransportKind::WebSocket, deadline, compression, authenticator, admission, cloned_tx).await,
+                            Ok(Err(e)) => eprintln!("tls handshake error, addr: {}, err: {}", addr, e),
+                            Err(_) => eprintln!("tls handshake timed out, addr: {}", addr),
+                        },
+                        None => handle_accept(stream, TransportKind::WebSocket, deadline, compression, authenticator, admission, cloned_tx).await,
+                    }
+                });
+            },
+            Err(e) => {
+                eprintln!("accept error occured!, err: {}", e);
+            },
+        }
+    }
+}
+
+/// Unix domain socket으로 들어오는 WebSocket 연결을 처리해주는 함수
+/// TCP 대신 Unix domain socket을 사용하는 것 외에는 `handle_websocket`과 같다. (IP가 없으므로 IP별 제한은 없음)
+#[cfg(unix)]
+async fn handle_unix_websocket(tx: Sender<ClientEventMessage>, compression: WebSocketCompression, authenticator: Arc<dyn Authenticator>, admission: Admission) {
+    // 이전 실행에서 남은 socket 파일이 있으면 bind가 실패하므로 지워준다.
+    let _ = std::fs::remove_file(UNIX_SOCKET_PATH);
+    let unix_listener = tokio::net::UnixListener::bind(UNIX_SOCKET_PATH).unwrap();
+
+    loop {
+        let cloned_tx = tx.clone();
+        match unix_listener.accept().await {
+            Ok((stream, _)) => {
+                let compression = compression.clone();
+                let authenticator = authenticator.clone();
+                let admission = admission.clone();
+                tokio::spawn(async move {
+                    let deadline = tokio::time::Instant::now() + admission.handshake_timeout();
+                    handle_accept(stream, TransportKind::UnixSocket, deadline, compression, authenticator, admission, cloned_tx).await;
                 });
             },
             Err(e) => {
@@ -214,22 +486,92 @@ async fn handle_websocket(tx: Sender<ClientEventMessage>) {
     }
 }
 
+#[cfg(not(unix))]
+async fn handle_unix_websocket(_tx: Sender<ClientEventMessage>, _compression: WebSocketCompression, _authenticator: Arc<dyn Authenticator>, _admission: Admission) {
+    eprintln!("unix domain socket is not supported on this platform: {}", UNIX_SOCKET_PATH);
+}
+
 /// 
 /// 연결된 각 클라이언트마다 task로 존재함.
-async fn handle_accept(stream: tokio::net::TcpStream, tx: Sender<ClientEventMessage>) {
+/// TCP, Unix domain socket 모두 같은 WebSocket 처리 로직을 사용한다.
+///
+/// handshake 요청의 토큰(`?token=...` 또는 `Authorization: Bearer ...`)을 `authenticator`로 검증하고,
+/// 실패하면 401 응답에 거절 사유를 담아 보내고 엔티티를 만들지 않은 채로 연결을 끊는다.
+/// handshake가 `handshake_deadline`(연결을 받은 시점 + `handshake_timeout`, TLS handshake 포함)까지 끝나지 않거나 서버가 가득 차 있어도 엔티티를 만들지 않는다.
+///
+/// `max_message_size`보다 큰 메시지를 보내거나 수신 token bucket을 넘겨서 메시지를 보내면 연결을 끊는다.
+///
+/// 클라이언트가 handshake에서 압축을 지원한다고 알리면(`COMPRESSION_HEADER`) 서버 설정에 따라 큰 frame을 압축해서 보낸다.
+async fn handle_accept<S>(stream: S, transport: TransportKind, handshake_deadline: tokio::time::Instant, compression: WebSocketCompression, authenticator: Arc<dyn Authenticator>, admission: Admission, tx: Sender<ClientEventMessage>)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
     println!("[Websocket Recv] start handle websocket strream");
-    let ws_stream = tokio_tungstenite::accept_async(stream).await.unwrap();
+    let mut account = None;
+    let mut compress = false;
+    let authenticate = |request: &Request, mut response: Response| -> Result<Response, ErrorResponse> {
+        let authorization = request.headers().get(AUTHORIZATION).and_then(|value| value.to_str().ok());
+        let token = auth::token_from_request(&request.uri().to_string(), authorization);
+
+        match authenticator.authenticate(token.as_deref()) {
+            Ok(account_id) => {
+                account = Some(AccountId(account_id));
+
+                let accepts_deflate = request.headers().get(COMPRESSION_HEADER).is_some_and(|value| value == DEFLATE);
+                if compression.settings.enabled && accepts_deflate {
+                    compress = true;
+                    response.headers_mut().insert(COMPRESSION_HEADER, HeaderValue::from_static(DEFLATE));
+                }
+                Ok(response)
+            },
+            Err(reason) => {
+                println!("[Websocket Recv] client rejected, reason: {}", reason);
+                let mut error_response = ErrorResponse::new(Some(reason));
+                *error_response.status_mut() = StatusCode::UNAUTHORIZED;
+                Err(error_response)
+            },
+        }
+    };
+
+    let config = WebSocketConfig::default()
+        .max_message_size(Some(admission.max_message_size()))
+        .max_frame_size(Some(admission.max_message_size()));
+    let handshake = tokio_tungstenite::accept_hdr_async_with_config(stream, authenticate, Some(config));
+    let ws_stream = match tokio::time::timeout_at(handshake_deadline, handshake).await {
+        Ok(Ok(ws_stream)) => ws_stream,
+        Ok(Err(e)) => {
+            eprintln!("[Websocket Recv] handshake error: {}", e);
+            return;
+        },
+        Err(_) => {
+            eprintln!("[Websocket Recv] handshake timed out");
+            return;
+        },
+    };
+    let Some(account) = account else {
+        return;
+    };
+
+    // 연결이 끝날 때까지 플레이어 수에 포함된다.
+    let _player_permit = match admission.admit_player() {
+        Ok(permit) => permit,
+        Err(reason) => {
+            println!("[Websocket Recv] client rejected, reason: {}", reason);
+            reject_websocket(ws_stream, reason).await;
+            return;
+        },
+    };
 
     // uuid generate
     let uuid = uuid::Uuid::new_v4();
-    println!("uuid: {}", uuid);
+    println!("uuid: {}, account: {}", uuid, account.0);
 
-    let (sink, stream) = ws_stream.split();
-    let (sink_tx, sink_recv) = tokio::sync::mpsc::channel::<Message>(100);
+    let (sink, mut stream) = ws_stream.split();
+    let (sink_tx, sink_recv) = outbound_queue(OUTBOUND_QUEUE_CAPACITY);
 
     // -------- Entity를 생성하기 위해서 메시지를 보내준다? 
     // Uuid는 Clone, Copy가 구현되어있으므로 자동으로 값복사가 일어나서 소유권 이동이 발생하지 않는다.
-    match tx.send(ClientEventMessage::Connect(ClientConnectInfo::new(uuid, sink_tx))).await {
+    match tx.send(ClientEventMessage::Connect(ClientConnectInfo::new(uuid, account, sink_tx, transport))).await {
         Ok(_) => {
             
         },
@@ -239,43 +581,99 @@ async fn handle_accept(stream: tokio::net::TcpStream, tx: Sender<ClientEventMess
         },
     }
     
-    let cloned_tx = tx.clone();
-    let stream_future = stream.try_for_each(|msg| {
-        if msg.is_empty() {
-            return futures_util::future::ok(());
-        }
-
-        println!("message recevied!, msg: {}", msg);
-
-        // 여기서 만약에 msg가 연결에 대한 요청이라면 entity를 만들어주고 
-        // 다른 내용이라면 내용에 따라서 처리를 해주어야한다.
-        let tx_in_future = cloned_tx.clone();
-        tokio::spawn(async move {
-            let msg_str = msg.to_text().unwrap();
-            if let Err(e) = tx_in_future.send(ClientEventMessage::Move(msg_str.into(), uuid)).await {
-                eprintln!("ClientEventMessage send error: {}", e);
-            }
-        });
-        
-        futures_util::future::ok(())
-    });
-
     // sink task generate
+    let compression_threshold = compress.then_some(compression.settings.threshold);
+    let compression_stats = compression.stats.clone();
     tokio::spawn(async move {
         println!("sink loop start!");
-        sink_handler(sink_recv, sink).await;
+        sink_handler(sink_recv, sink, compression_threshold, &compression_stats).await;
+        println!("[compression] {}", compression_stats.summary());
     });
 
-    stream_future.await.unwrap();
+    let mut rate_limiter = admission.rate_limiter();
+    while let Some(result) = stream.next().await {
+        let msg = match result {
+            Ok(msg) => msg,
+            Err(tungstenite::Error::Capacity(e)) => {
+                let count = admission.record_violation(Violation::Oversized);
+                println!("[Websocket Recv] disconnect client, uuid: {}, reason: {} (oversized total: {})", uuid, e, count);
+                break;
+            },
+            Err(e) => {
+                eprintln!("[Websocket Recv] stream error: {}", e);
+                break;
+            },
+        };
+
+        if msg.is_empty() {
+            continue;
+        }
+
+        if !rate_limiter.allow(Instant::now()) {
+            let count = admission.record_violation(Violation::RateLimited);
+            println!("[Websocket Recv] disconnect client, uuid: {}, reason: too many messages (rate limited total: {})", uuid, count);
+            break;
+        }
+
+        // 받은 순서대로 bevy에 전달한다.
+        // bevy 쪽 채널이 가득 차면 여기서 기다리므로 이 연결의 수신도 같이 멈춘다.
+        let Ok(msg_str) = msg.to_text() else {
+            continue;
+        };
+        let client_msg = match serde_json::from_str::<ClientMessage>(msg_str) {
+            Ok(client_msg) => client_msg,
+            Err(e) => {
+                eprintln!("[Websocket Recv] invalid message, uuid: {}, error: {}", uuid, e);
+                continue;
+            },
+        };
+        if let Err(e) = tx.send(ClientEventMessage::Event(client_msg, uuid)).await {
+            eprintln!("ClientEventMessage send error: {}", e);
+            break;
+        }
+    }
+
+    // 연결이 끊어졌으므로 client entity를 제거하도록 요청한다.
+    if let Err(e) = tx.send(ClientEventMessage::Disconnect(uuid)).await {
+        eprintln!("ClientEventMessage send error: {}", e);
+    }
 
     println!("[Websocket Recv] finish handle websocket strream");
 }
 
+/// `ServerMessage::Rejected`를 보내고 거절 사유와 함께 연결을 닫는다.
+async fn reject_websocket<S>(mut ws_stream: WebSocketStream<S>, reason: String)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let rejected = ServerMessage::Rejected { reason: reason.clone() };
+    let outgoing = encode_message(&mut ChannelSequencer::default(), rejected.default_channel(), rejected);
+    if let Err(e) = ws_stream.send(Message::text(encode_packet(&outgoing))).await {
+        eprintln!("[reject_websocket] send error: {}", e);
+        return;
+    }
+    let _ = ws_stream.close(Some(CloseFrame { code: CloseCode::Again, reason: reason.into() })).await;
+}
+
 /// sink handler 
-/// 
-async fn sink_handler(mut recv: Receiver<Message>, mut sink: SplitSink<WebSocketStream<TcpStream>, Message>) {
+/// 연결마다 하나뿐인 writer task, 송신 대기열에 쌓인 메시지를 넣은 순서대로 하나의 frame으로 묶어서 보낸다.
+/// `compression_threshold`가 있으면 그 크기 이상인 frame은 압축해서 보낸다.
+/// 대기열이 넘쳐서 닫혔으면 close frame을 보내 연결을 끊는다.
+async fn sink_handler<S>(mut recv: OutboundReceiver, mut sink: SplitSink<WebSocketStream<S>, Message>, compression_threshold: Option<usize>, stats: &CompressionStats)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
     println!("wait for recv sink message");
-    while let Some(msg) = recv.recv().await {
-        sink.send(msg).await.unwrap();
+    while let Some(pending) = recv.recv().await {
+        let frame = encode_batch(&pending);
+        if let Err(e) = sink.send(compress_frame(frame, compression_threshold, stats)).await {
+            eprintln!("[sink_handler] send error: {}", e);
+            return;
+        }
+    }
+
+    if recv.overflowed() {
+        let close = CloseFrame { code: CloseCode::Policy, reason: "send queue overflow".into() };
+        let _ = sink.send(Message::Close(Some(close))).await;
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,184 @@
+use std::{collections::HashMap, net::IpAddr, sync::{atomic::{AtomicU64, Ordering}, Arc, Mutex}, time::{Duration, Instant}};
+
+/// 접속 제한 설정
+pub struct ConnectionLimits {
+    /// 동시에 접속할 수 있는 최대 플레이어 수
+    pub max_players: usize,
+    /// IP 하나에서 동시에 맺을 수 있는 최대 연결 수 (handshake 중인 연결 포함)
+    pub max_connections_per_ip: usize,
+    /// 연결을 받은 뒤 TLS, WebSocket handshake를 모두 끝내야 하는 시간
+    pub handshake_timeout: Duration,
+    /// 클라이언트가 보내는 메시지(WebSocket frame 포함) 최대 크기
+    pub max_message_size: usize,
+    /// 연결마다 초당 받을 수 있는 메시지 수와 순간적으로 허용하는 최대 메시지 수 (token bucket)
+    pub messages_per_second: f32,
+    pub message_burst: f32,
+    /// UDP 연결마다 초당 받을 수 있는 datagram 수와 최대 burst (ack, keep alive, 재전송 포함)
+    pub datagrams_per_second: f32,
+    pub datagram_burst: f32,
+}
+
+impl Default for ConnectionLimits {
+    fn default() -> Self {
+        ConnectionLimits {
+            max_players: 64,
+            max_connections_per_ip: 4,
+            handshake_timeout: Duration::from_secs(5),
+            max_message_size: 4 * 1024,
+            messages_per_second: 60.0,
+            message_burst: 120.0,
+            datagrams_per_second: 240.0,
+            datagram_burst: 480.0,
+        }
+    }
+}
+
+/// 접속 허용 여부를 판단하고 현재 연결 수를 세는 역할
+/// 모든 listener task가 clone해서 공유하며, 허용되면 `AdmissionPermit`을 돌려준다.
+/// permit은 연결이 끝날 때 drop되면서 자리를 반환한다.
+#[derive(Clone)]
+pub struct Admission {
+    limits: Arc<ConnectionLimits>,
+    state: Arc<Mutex<AdmissionState>>,
+    metrics: Arc<ViolationMetrics>,
+}
+
+/// 수신 제한을 어겨서 끊은 연결 수
+#[derive(Default)]
+pub struct ViolationMetrics {
+    oversized: AtomicU64,
+    rate_limited: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Violation {
+    /// `max_message_size`보다 큰 메시지
+    Oversized,
+    /// token bucket이 비었는데 메시지를 보냄
+    RateLimited,
+}
+
+/// 연결마다 가지는 수신 token bucket
+/// 메시지 하나에 token 하나를 사용하고, 초당 `messages_per_second`만큼 `message_burst`까지 채워진다.
+pub struct InboundRateLimiter {
+    tokens: f32,
+    per_second: f32,
+    burst: f32,
+    last: Instant,
+}
+
+impl InboundRateLimiter {
+    pub fn allow(&mut self, now: Instant) -> bool {
+        let elapsed = now.duration_since(self.last).as_secs_f32();
+        self.last = now;
+        self.tokens = (self.tokens + elapsed * self.per_second).min(self.burst);
+
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+#[derive(Default)]
+struct AdmissionState {
+    players: usize,
+    connections_per_ip: HashMap<IpAddr, usize>,
+}
+
+enum PermitKind {
+    Connection(IpAddr),
+    Player,
+}
+
+pub struct AdmissionPermit {
+    admission: Admission,
+    kind: PermitKind,
+}
+
+impl Admission {
+    pub fn new(limits: ConnectionLimits) -> Self {
+        Admission {
+            limits: Arc::new(limits),
+            state: Arc::new(Mutex::new(AdmissionState::default())),
+            metrics: Arc::new(ViolationMetrics::default()),
+        }
+    }
+
+    pub fn handshake_timeout(&self) -> Duration {
+        self.limits.handshake_timeout
+    }
+
+    pub fn max_message_size(&self) -> usize {
+        self.limits.max_message_size
+    }
+
+    pub fn rate_limiter(&self) -> InboundRateLimiter {
+        InboundRateLimiter {
+            tokens: self.limits.message_burst,
+            per_second: self.limits.messages_per_second,
+            burst: self.limits.message_burst,
+            last: Instant::now(),
+        }
+    }
+
+    /// 메시지로 전달되지 않는 datagram(ack, 중복, 순서가 맞지 않는 메시지)도 세기 위한 UDP용 token bucket
+    pub fn datagram_rate_limiter(&self) -> InboundRateLimiter {
+        InboundRateLimiter {
+            tokens: self.limits.datagram_burst,
+            per_second: self.limits.datagrams_per_second,
+            burst: self.limits.datagram_burst,
+            last: Instant::now(),
+        }
+    }
+
+    /// 수신 제한 위반으로 연결을 끊을 때 호출한다. 지금까지 같은 이유로 끊은 연결 수를 돌려준다.
+    pub fn record_violation(&self, violation: Violation) -> u64 {
+        let counter = match violation {
+            Violation::Oversized => &self.metrics.oversized,
+            Violation::RateLimited => &self.metrics.rate_limited,
+        };
+        counter.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// 연결을 받자마자 IP별 연결 수를 확인한다.
+    pub fn admit_connection(&self, ip: IpAddr) -> Result<AdmissionPermit, String> {
+        let mut state = self.state.lock().unwrap();
+        let count = state.connections_per_ip.entry(ip).or_insert(0);
+        if *count >= self.limits.max_connections_per_ip {
+            return Err(format!("too many connections from {}", ip));
+        }
+        *count += 1;
+
+        Ok(AdmissionPermit { admission: self.clone(), kind: PermitKind::Connection(ip) })
+    }
+
+    /// handshake와 인증이 끝나고 플레이어 엔티티를 만들기 직전에 확인한다.
+    pub fn admit_player(&self) -> Result<AdmissionPermit, String> {
+        let mut state = self.state.lock().unwrap();
+        if state.players >= self.limits.max_players {
+            return Err(format!("server is full ({} players)", self.limits.max_players));
+        }
+        state.players += 1;
+
+        Ok(AdmissionPermit { admission: self.clone(), kind: PermitKind::Player })
+    }
+}
+
+impl Drop for AdmissionPermit {
+    fn drop(&mut self) {
+        let mut state = self.admission.state.lock().unwrap();
+        match self.kind {
+            PermitKind::Connection(ip) => {
+                if let Some(count) = state.connections_per_ip.get_mut(&ip) {
+                    *count -= 1;
+                    if *count == 0 {
+                        state.connections_per_ip.remove(&ip);
+                    }
+                }
+            },
+            PermitKind::Player => state.players -= 1,
+        }
+    }
+}
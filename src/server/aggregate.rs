@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::common::{ChannelKind, ChannelSequencer, ServerMessage};
+
+use super::{encode_message, send_messages, ClientSender, ReplicationSet};
+
+/// 송신 메시지 모으기
+/// 한 tick 동안 여러 시스템이 클라이언트에게 보낼 메시지를 `OutboundBuffer`에 넣고,
+/// tick 마지막(`ReplicationSet::Flush`)에 클라이언트마다 한 번에 송신 대기열로 보낸다.
+pub struct AggregatePlugin;
+
+impl Plugin for AggregatePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PostUpdate, flush_outbound_system.in_set(ReplicationSet::Flush));
+    }
+}
+
+// ----------------- component
+
+/// 클라이언트에게 이번 tick에 보낼 메시지와 보낼 채널
+/// `UnreliableSequenced` 채널로 보내는 같은 엔티티에 대한 update(`ServerMessage::supersede_key`)는 가장 최신 상태 하나로 합쳐진다.
+#[derive(Component, Default)]
+pub struct OutboundBuffer {
+    /// spawn, despawn 처럼 합치지 않는 메시지, 넣은 순서대로 보낸다.
+    messages: Vec<(ChannelKind, ServerMessage)>,
+    /// 엔티티별 최신 update, 처음 넣은 순서대로 보낸다.
+    updates: Vec<ServerMessage>,
+    update_index: HashMap<u64, usize>,
+}
+
+impl OutboundBuffer {
+    /// 메시지 종류별 기본 채널(`ServerMessage::default_channel`)로 보낸다.
+    pub fn push(&mut self, server_msg: ServerMessage) {
+        self.push_on(server_msg.default_channel(), server_msg);
+    }
+
+    pub fn push_on(&mut self, channel: ChannelKind, server_msg: ServerMessage) {
+        let key = server_msg.supersede_key().filter(|_| channel == ChannelKind::UnreliableSequenced);
+        let Some(key) = key else {
+            self.messages.push((channel, server_msg));
+            return;
+        };
+
+        match self.update_index.get(&key) {
+            Some(&index) => self.updates[index].coalesce(server_msg),
+            None => {
+                self.update_index.insert(key, self.updates.len());
+                self.updates.push(server_msg);
+            },
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty() && self.updates.is_empty()
+    }
+
+    /// 합치지 않는 메시지를 먼저, 그 다음 update를 꺼낸다.
+    fn take(&mut self) -> Vec<(ChannelKind, ServerMessage)> {
+        self.update_index.clear();
+        let mut messages = std::mem::take(&mut self.messages);
+        messages.extend(self.updates.drain(..).map(|server_msg| (ChannelKind::UnreliableSequenced, server_msg)));
+        messages
+    }
+}
+
+// ----------------- system
+
+/// 모아둔 메시지에 채널 순서 번호를 붙여서 송신 대기열로 보낸다.
+fn flush_outbound_system(mut clients: Query<(&ClientSender, &mut ChannelSequencer, &mut OutboundBuffer)>) {
+    for (sender, mut sequencer, mut buffer) in clients.iter_mut() {
+        if buffer.is_empty() {
+            continue;
+        }
+
+        let messages = buffer.take().into_iter()
+            .map(|(channel, server_msg)| encode_message(&mut sequencer, channel, server_msg))
+            .collect();
+        send_messages(&sender.0, messages);
+    }
+}
@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+/// 접속 인증
+/// WebSocket은 handshake 요청의 query string(`?token=...`) 또는 `Authorization: Bearer ...` header,
+/// UDP는 `ChallengeResponse`에 담긴 토큰을 검증한다.
+/// 인증에 실패하면 엔티티를 만들기 전에 거절 사유와 함께 연결을 끊는다.
+pub trait Authenticator: Send + Sync + 'static {
+    /// 토큰을 검증하고 계정 id를 돌려준다. 실패하면 거절 사유를 돌려준다.
+    fn authenticate(&self, token: Option<&str>) -> Result<String, String>;
+}
+
+/// 인증된 계정 id, `Client(Uuid)`와 함께 플레이어 엔티티에 붙는다.
+#[derive(Component, Clone, Debug)]
+pub struct AccountId(pub String);
+
+/// 개발용, 모든 접속을 허용한다. 토큰이 있으면 토큰을 계정 id로 사용한다.
+pub struct GuestAuthenticator;
+
+impl Authenticator for GuestAuthenticator {
+    fn authenticate(&self, token: Option<&str>) -> Result<String, String> {
+        Ok(token.unwrap_or("guest").to_string())
+    }
+}
+
+/// 미리 등록된 토큰만 허용한다.
+/// 파일은 한 줄에 `토큰 계정id` 형식이며 `#`으로 시작하는 줄은 무시한다.
+pub struct StaticTokenAuthenticator(HashMap<String, String>);
+
+impl StaticTokenAuthenticator {
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path).map_err(|e| format!("fail to read token file {}, error: {}", path, e))?;
+
+        let tokens = contents.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| line.split_once(char::is_whitespace))
+            .map(|(token, account)| (token.to_string(), account.trim().to_string()))
+            .collect();
+
+        Ok(StaticTokenAuthenticator(tokens))
+    }
+}
+
+impl Authenticator for StaticTokenAuthenticator {
+    fn authenticate(&self, token: Option<&str>) -> Result<String, String> {
+        let token = token.ok_or("missing token")?;
+        self.0.get(token).cloned().ok_or("invalid token".to_string())
+    }
+}
+
+/// handshake 요청에서 토큰을 찾는다. query string의 `token`을 먼저 보고 없으면 `Authorization` header를 본다.
+/// query string의 값은 percent-encoding(`%2B`, `+` 등)을 풀어서 돌려준다.
+pub fn token_from_request(uri: &str, authorization: Option<&str>) -> Option<String> {
+    let query_token = uri.split_once('?')
+        .and_then(|(_, query)| form_urlencoded::parse(query.as_bytes()).find(|(key, _)| key == "token"))
+        .map(|(_, token)| token.into_owned())
+        .filter(|token| !token.is_empty());
+
+    query_token
+        .or_else(|| authorization.and_then(|value| value.strip_prefix("Bearer ")).map(str::to_string))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// query string의 토큰은 percent-encoding을 풀고, 없으면 `Authorization` header를 사용한다.
+    #[test]
+    fn query_token_is_percent_decoded() {
+        assert_eq!(token_from_request("/?room=1&token=a%2Bb%2Fc%3D%3D", None).as_deref(), Some("a+b/c=="));
+        assert_eq!(token_from_request("/?token=two+words", None).as_deref(), Some("two words"));
+        assert_eq!(token_from_request("/?token=", Some("Bearer header")).as_deref(), Some("header"));
+        assert_eq!(token_from_request("/", None), None);
+    }
+}
@@ -0,0 +1,47 @@
+use bevy::prelude::*;
+
+use crate::common::{ChannelKind, TimePing, TimePong};
+
+use super::event::{ClientEventAppExt, FromClient, SendTarget, ServerEvent, ServerEventAppExt};
+
+/// 서버 tick과 시간 동기화
+/// 고정 tick마다 `ServerTick`을 올리고, 클라이언트의 `TimePing`에 현재 tick으로 바로 응답한다.
+pub struct ClockPlugin;
+
+impl Plugin for ClockPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ServerTick>()
+            .add_client_event::<TimePing>()
+            .add_server_event::<TimePong>()
+            .add_systems(FixedFirst, advance_tick_system)
+            .add_systems(Update, time_pong_system);
+    }
+}
+
+// ----------------- resource
+
+/// 서버가 시작된 뒤 지난 고정 tick 수
+#[derive(Resource, Default, Debug, Clone, Copy)]
+pub struct ServerTick(pub u32);
+
+// ----------------- system
+
+fn advance_tick_system(mut tick: ResMut<ServerTick>) {
+    tick.0 = tick.0.wrapping_add(1);
+}
+
+fn time_pong_system(
+    tick: Res<ServerTick>,
+    fixed_time: Res<Time<Fixed>>,
+    mut pings: EventReader<FromClient<TimePing>>,
+    mut pongs: EventWriter<ServerEvent<TimePong>>,
+) {
+    for ping in pings.read() {
+        // 앞선 reliable 메시지를 기다리지 않도록 순서를 보장하지 않는 채널로 보낸다.
+        pongs.write(ServerEvent {
+            target: SendTarget::Only(ping.uuid),
+            channel: ChannelKind::ReliableUnordered,
+            event: TimePong { client_time: ping.event.client_time, server_tick: tick.0, overstep: fixed_time.overstep_fraction() },
+        });
+    }
+}
@@ -0,0 +1,252 @@
+use std::{hash::{DefaultHasher, Hash, Hasher}, time::{SystemTime, UNIX_EPOCH}};
+
+use bevy::{prelude::*, time::TimeUpdateStrategy};
+use uuid::Uuid;
+
+use crate::common::{ClientMessage, InputPacket, NetworkMessage, PlayerInput, TransportKind, TICK_RATE};
+
+use super::{auth::AccountId, clock::ServerTick, movement::Velocity, outbound::{outbound_queue, OUTBOUND_QUEUE_CAPACITY}, replay::MessageRecorder, server_app, Client, ClientConnectInfo, ClientEventMessage, ClientMessageHandler, SimulationSet};
+
+/// 결정성 검사에 사용하는 가상 클라이언트 수와 실행할 tick 수
+const CHECK_CLIENTS: u128 = 4;
+const CHECK_TICKS: u32 = 300;
+
+/// 결정적 시뮬레이션
+/// seed가 있으면 클라이언트 메시지를 받은 즉시 처리하지 않고 `PendingClientMessages`에 모아두었다가
+/// 다음 고정 tick을 시작할 때(`SimulationSet::Receive`) 클라이언트 id 순서로 처리한다.
+/// 시뮬레이션에서 사용하는 난수는 모두 seed로 초기화한 `SimulationRng`에서 꺼내므로
+/// 같은 tick에 같은 메시지가 들어오면 도착 순서와 프레임 시간에 상관없이 항상 비트 단위로 같은 월드 상태가 된다.
+/// seed가 없으면 시간으로 `SimulationRng`를 초기화하고 메시지는 받은 즉시 처리한다.
+pub struct DeterministicPlugin {
+    pub seed: Option<u64>,
+}
+
+impl Plugin for DeterministicPlugin {
+    fn build(&self, app: &mut App) {
+        match self.seed {
+            Some(seed) => {
+                println!("[deterministic] enabled, seed: {}", seed);
+                app.insert_resource(SimulationSeed(seed))
+                    .insert_resource(SimulationRng::new(seed))
+                    .init_resource::<PendingClientMessages>()
+                    .add_systems(FixedUpdate, apply_pending_messages_system.in_set(SimulationSet::Receive));
+            },
+            None => {
+                let seed = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_nanos() as u64);
+                app.insert_resource(SimulationSeed(seed))
+                    .insert_resource(SimulationRng::new(seed));
+            },
+        }
+    }
+}
+
+// ----------------- resource
+
+/// `SimulationRng`를 초기화한 seed, 기록 파일(`replay`)에 같이 저장한다.
+#[derive(Resource, Clone, Copy)]
+pub struct SimulationSeed(pub u64);
+
+/// 시뮬레이션 난수 (splitmix64), 시뮬레이션 결과에 영향을 주는 난수는 모두 여기서 꺼낸다.
+#[derive(Resource, Clone)]
+pub struct SimulationRng(u64);
+
+impl SimulationRng {
+    pub fn new(seed: u64) -> Self {
+        SimulationRng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// [min, max)
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+}
+
+/// 결정적 모드에서 다음 고정 tick에 처리할 클라이언트 메시지 (받은 순서)
+#[derive(Resource, Default)]
+pub struct PendingClientMessages(Vec<ClientEventMessage>);
+
+impl PendingClientMessages {
+    pub fn push(&mut self, msg: ClientEventMessage) {
+        self.0.push(msg);
+    }
+}
+
+// ----------------- system
+
+/// 같은 클라이언트의 메시지는 받은 순서를 유지한다. (stable sort)
+/// 이번 tick을 시뮬레이션하기 전에 처리하므로 현재 tick으로 기록한다.
+fn apply_pending_messages_system(
+    tick: Res<ServerTick>,
+    mut pending: ResMut<PendingClientMessages>,
+    mut recorder: Option<ResMut<MessageRecorder>>,
+    mut handler: ClientMessageHandler,
+) {
+    let mut messages = std::mem::take(&mut pending.0);
+    messages.sort_by_key(|msg| msg.uuid());
+
+    for msg in messages {
+        if let Some(recorder) = recorder.as_mut() {
+            recorder.record(tick.0, &msg);
+        }
+        handler.handle(msg);
+    }
+}
+
+// ----------------- headless server
+
+/// 소켓 없이 고정 tick 단위로 진행시키는 서버 (결정성 검사, 기록 재생)
+/// 매 `update`마다 정확히 고정 tick 하나만큼 시간을 진행시키고, tick을 시작하기 전에 그 tick의 메시지를 넣는다.
+pub struct HeadlessServer {
+    app: App,
+}
+
+impl HeadlessServer {
+    pub fn new(seed: u64) -> Self {
+        let mut app = server_app(Some(seed));
+        let (_, receiver) = tokio::sync::mpsc::channel(1);
+        app.insert_resource(super::WebSocketAcceptEvent(receiver))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Time::<Fixed>::from_hz(TICK_RATE).timestep()));
+        app.finish();
+        app.cleanup();
+
+        HeadlessServer { app }
+    }
+
+    /// 마지막으로 진행한 tick
+    pub fn tick(&self) -> u32 {
+        self.app.world().resource::<ServerTick>().0
+    }
+
+    /// `messages`를 다음 tick 시작 시 처리하도록 넣고 고정 tick 하나를 진행시킨다.
+    pub fn step(&mut self, messages: impl IntoIterator<Item = ClientEventMessage>) {
+        let next_tick = self.tick() + 1;
+        self.app.world_mut().resource_mut::<PendingClientMessages>().0.extend(messages);
+
+        // 처음 update에서는 시간이 흐르지 않아서 고정 tick이 돌지 않는다.
+        while self.tick() < next_tick {
+            self.app.update();
+        }
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        self.app.world_mut()
+    }
+}
+
+// ----------------- determinism check
+
+/// 소켓 없이 같은 입력 기록으로 시뮬레이션을 여러 번 돌려서 월드 상태가 같은지 확인한다. (`cargo run determinism --seed 42`)
+/// 1. 기록 순서대로 넣은 결과
+/// 2. 같은 tick 안의 메시지 순서를 뒤집어서 넣은 결과 -> 1과 같아야 한다.
+/// 3. 다른 seed로 돌린 결과 -> 스폰 위치가 달라지므로 1과 달라야 한다.
+pub fn run_determinism_check(seed: u64) -> Result<(), String> {
+    let first = simulate(seed, seed, false);
+    let reversed = simulate(seed, seed, true);
+    let other_seed = simulate(seed, seed.wrapping_add(1), false);
+
+    println!("[determinism] seed: {}, clients: {}, ticks: {}", seed, CHECK_CLIENTS, CHECK_TICKS);
+    println!("[determinism] run 1 (arrival order): {:016x}", first);
+    println!("[determinism] run 2 (reversed arrival order in each tick): {:016x}", reversed);
+    println!("[determinism] run 3 (seed {}): {:016x}", seed.wrapping_add(1), other_seed);
+
+    if first != reversed {
+        return Err("same input log produced different world state".to_string());
+    }
+    if first == other_seed {
+        return Err("world state does not depend on the seed".to_string());
+    }
+    println!("[determinism] OK");
+    Ok(())
+}
+
+fn simulate(script_seed: u64, seed: u64, reverse_arrival: bool) -> u64 {
+    let mut server = HeadlessServer::new(seed);
+
+    let mut script = scripted_messages(script_seed).into_iter().peekable();
+    for tick in 1..=CHECK_TICKS {
+        let mut messages: Vec<ClientEventMessage> = std::iter::from_fn(|| script.next_if(|(message_tick, _)| *message_tick == tick).map(|(_, msg)| msg)).collect();
+        if reverse_arrival {
+            messages.reverse();
+        }
+        server.step(messages);
+    }
+
+    world_hash(server.world_mut())
+}
+
+/// 클라이언트마다 tick당 메시지 하나 (접속, 입력, 접속 종료)
+/// 두 클라이언트씩 같은 tick에 접속하고, 첫번째 클라이언트는 중간에 접속을 끊는다.
+fn scripted_messages(seed: u64) -> Vec<(u32, ClientEventMessage)> {
+    let mut rng = SimulationRng::new(seed);
+    let mut messages = Vec::new();
+
+    for index in 0..CHECK_CLIENTS {
+        let uuid = Uuid::from_u128(index + 1);
+        let connect_tick = 1 + (index as u32 / 2) * 10;
+        let disconnect_tick = if index == 0 { CHECK_TICKS / 2 } else { CHECK_TICKS + 1 };
+
+        // 보낸 메시지는 받는 쪽이 없으므로 버려진다.
+        let (sender, _) = outbound_queue(OUTBOUND_QUEUE_CAPACITY);
+        let info = ClientConnectInfo::new(uuid, AccountId(format!("bot-{}", index)), sender, TransportKind::WebSocket);
+        messages.push((connect_tick, ClientEventMessage::Connect(info)));
+
+        let mut buttons = 0;
+        for tick in (connect_tick + 1)..disconnect_tick.min(CHECK_TICKS + 1) {
+            if rng.next_u64() % 15 == 0 {
+                buttons = (rng.next_u64() % 16) as u8;
+            }
+            let packet = InputPacket { tick, inputs: vec![PlayerInput { buttons }], view_tick: tick as f32 };
+            let client_msg = ClientMessage { name: InputPacket::NAME.to_string(), value: serde_json::to_value(&packet).unwrap() };
+            messages.push((tick, ClientEventMessage::Event(client_msg, uuid)));
+        }
+
+        if disconnect_tick <= CHECK_TICKS {
+            messages.push((disconnect_tick, ClientEventMessage::Disconnect(uuid)));
+        }
+    }
+
+    messages.sort_by_key(|(tick, _)| *tick);
+    messages
+}
+
+/// 클라이언트 id 순서로 위치, 속도, 입력의 비트 값을 hash
+pub fn world_hash(world: &mut World) -> u64 {
+    let mut players: Vec<(Uuid, [u32; 3], [u32; 2], u8)> = world.query::<(&Client, &Transform, &Velocity, &PlayerInput)>()
+        .iter(world)
+        .map(|(client, transform, velocity, input)| (client.0, transform.translation.to_array().map(f32::to_bits), velocity.0.to_array().map(f32::to_bits), input.buttons))
+        .collect();
+    players.sort_by_key(|player| player.0);
+
+    let mut hasher = DefaultHasher::new();
+    world.resource::<ServerTick>().0.hash(&mut hasher);
+    players.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_and_input_log_produce_same_world() {
+        assert_eq!(simulate(42, 42, false), simulate(42, 42, false));
+    }
+
+    #[test]
+    fn arrival_order_within_tick_does_not_change_world() {
+        assert_eq!(simulate(42, 42, false), simulate(42, 42, true));
+    }
+}
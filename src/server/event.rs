@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+use uuid::Uuid;
+
+use crate::common::{ChannelKind, ClientMessage, NetworkMessage, ServerMessage};
+
+use super::{aggregate::OutboundBuffer, Client, ReplicationSet};
+
+/// 서버 -> 클라이언트 event
+/// `app.add_server_event::<T>()`로 등록한 뒤 서버 system에서 `EventWriter<ServerEvent<T>>`로 보내면
+/// 대상 클라이언트는 같은 타입을 `add_server_event`로 등록해두고 `EventReader<T>`로 받는다.
+pub trait ServerEventAppExt {
+    fn add_server_event<T: Serialize + NetworkMessage + Send + Sync + 'static>(&mut self) -> &mut Self;
+}
+
+impl ServerEventAppExt for App {
+    fn add_server_event<T: Serialize + NetworkMessage + Send + Sync + 'static>(&mut self) -> &mut Self {
+        self.add_event::<ServerEvent<T>>()
+            .add_systems(PostUpdate, send_server_event_system::<T>.in_set(ReplicationSet::Send))
+    }
+}
+
+/// 클라이언트 -> 서버 event
+/// `app.add_client_event::<T>()`로 등록하면 클라이언트가 보낸 `T`를 서버 system에서 `EventReader<FromClient<T>>`로 받는다.
+/// 같은 이름을 두 번 등록하면 어느 타입으로 받을지 알 수 없으므로 panic한다.
+pub trait ClientEventAppExt {
+    fn add_client_event<T: DeserializeOwned + NetworkMessage + Send + Sync + 'static>(&mut self) -> &mut Self;
+}
+
+impl ClientEventAppExt for App {
+    fn add_client_event<T: DeserializeOwned + NetworkMessage + Send + Sync + 'static>(&mut self) -> &mut Self {
+        self.add_event::<FromClient<T>>().init_resource::<ClientEvents>();
+        let previous = self.world_mut().resource_mut::<ClientEvents>().0.insert(T::NAME, write_client_event::<T>);
+        assert!(previous.is_none(), "client event name is already registered: {}", T::NAME);
+        self
+    }
+}
+
+// ----------------- event
+
+#[derive(Event)]
+pub struct ServerEvent<T> {
+    pub target: SendTarget,
+    /// 보낼 채널, 보통은 `ChannelKind::ReliableOrdered`
+    pub channel: ChannelKind,
+    pub event: T,
+}
+
+/// event를 받을 클라이언트, `Client(Uuid)`의 uuid로 지정한다.
+#[derive(Debug, Clone)]
+pub enum SendTarget {
+    Only(Uuid),
+    List(Vec<Uuid>),
+    All,
+    AllExcept(Uuid),
+}
+
+impl SendTarget {
+    fn contains(&self, uuid: &Uuid) -> bool {
+        match self {
+            SendTarget::Only(target) => target == uuid,
+            SendTarget::List(targets) => targets.contains(uuid),
+            SendTarget::All => true,
+            SendTarget::AllExcept(except) => except != uuid,
+        }
+    }
+}
+
+/// 클라이언트가 보낸 event와 보낸 클라이언트 엔티티
+#[derive(Event)]
+pub struct FromClient<T> {
+    pub client: Entity,
+    pub uuid: Uuid,
+    pub event: T,
+}
+
+// ----------------- resource
+
+/// event 이름별로 받은 값을 `FromClient<T>` event로 보내는 함수
+#[derive(Resource, Default)]
+pub struct ClientEvents(HashMap<&'static str, fn(&mut Commands, Entity, Uuid, serde_json::Value) -> Result<(), serde_json::Error>>);
+
+impl ClientEvents {
+    /// 등록되지 않은 event는 무시한다.
+    pub fn apply(&self, commands: &mut Commands, client: Entity, uuid: Uuid, client_msg: ClientMessage) {
+        let Some(write) = self.0.get(client_msg.name.as_str()) else {
+            eprintln!("[client_event] unregistered event: {}, uuid: {}", client_msg.name, uuid);
+            return;
+        };
+
+        if let Err(e) = write(commands, client, uuid, client_msg.value) {
+            eprintln!("[client_event] failed to deserialize {}, uuid: {}, error: {}", client_msg.name, uuid, e);
+        }
+    }
+}
+
+fn write_client_event<T: DeserializeOwned + Send + Sync + 'static>(commands: &mut Commands, client: Entity, uuid: Uuid, value: serde_json::Value) -> Result<(), serde_json::Error> {
+    commands.send_event(FromClient { client, uuid, event: serde_json::from_value::<T>(value)? });
+    Ok(())
+}
+
+// ----------------- system
+
+/// event마다 한 번만 직렬화해서 대상 클라이언트의 `OutboundBuffer`에 넣는다.
+fn send_server_event_system<T: Serialize + NetworkMessage + Send + Sync + 'static>(
+    mut events: EventReader<ServerEvent<T>>,
+    mut clients: Query<(&Client, &mut OutboundBuffer)>,
+) {
+    for server_event in events.read() {
+        let value = match serde_json::to_value(&server_event.event) {
+            Ok(value) => value,
+            Err(e) => {
+                eprintln!("[server_event] failed to serialize {}: {}", T::NAME, e);
+                continue;
+            },
+        };
+
+        for (client, mut buffer) in clients.iter_mut() {
+            if server_event.target.contains(&client.0) {
+                buffer.push_on(server_event.channel, ServerMessage::Event { name: T::NAME.to_string(), value: value.clone() });
+            }
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::{HashMap, VecDeque};
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::common::{InputPacket, TICK_RATE};
+
+use super::{clock::ServerTick, event::FromClient, interest::Replicated, SimulationSet};
+
+/// 되감을 수 있는 최대 tick 수 (500ms), 지연이 이보다 큰 클라이언트는 이 시점으로 판정한다.
+pub const MAX_REWIND_TICKS: u32 = (TICK_RATE / 2.0) as u32;
+
+/// 지연 보상(lag compensation)
+/// 복제 대상 엔티티마다 최근 `MAX_REWIND_TICKS`동안의 `Transform`을 tick별로 기록해두고,
+/// 클라이언트가 행동할 때 보고 있던 tick으로 되감아서 판정할 수 있게 한다. (`LagCompensation`)
+/// 클라이언트가 보고 있던 tick은 매 tick 보내는 `InputPacket::view_tick`으로 알 수 있다.
+pub struct LagCompensationPlugin;
+
+impl Plugin for LagCompensationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, (
+            record_view_tick_system.in_set(SimulationSet::Input),
+            record_history_system.in_set(SimulationSet::Record),
+        ));
+    }
+}
+
+// ----------------- component
+
+/// tick이 끝났을 때의 `Transform` 기록 (오래된 기록이 앞)
+#[derive(Component, Default)]
+pub struct TransformHistory(VecDeque<(u32, Transform)>);
+
+impl TransformHistory {
+    /// 기록 사이의 tick이면 보간한다. 기록보다 이전이면 가장 오래된 기록을 사용한다.
+    pub fn at(&self, tick: f32) -> Option<Transform> {
+        let after = self.0.iter().position(|(recorded, _)| *recorded as f32 >= tick);
+        match after {
+            Some(0) => self.0.front().map(|(_, transform)| *transform),
+            Some(index) => {
+                let (before_tick, before) = self.0[index - 1];
+                let (after_tick, after) = self.0[index];
+                let t = (tick - before_tick as f32) / (after_tick - before_tick) as f32;
+                Some(Transform {
+                    translation: before.translation.lerp(after.translation, t),
+                    rotation: before.rotation.slerp(after.rotation, t),
+                    scale: before.scale.lerp(after.scale, t),
+                })
+            },
+            None => self.0.back().map(|(_, transform)| *transform),
+        }
+    }
+}
+
+/// 클라이언트 엔티티에 붙는, 마지막으로 받은 `InputPacket::view_tick`
+#[derive(Component, Clone, Copy)]
+pub struct ViewTick(pub f32);
+
+// ----------------- system param
+
+/// "tick T 시점"의 복제 대상 엔티티 `Transform` 조회
+/// 클라이언트 event를 판정할 때 `rewind_tick_for`로 보낸 클라이언트가 보고 있던 tick을 구해서 사용한다.
+/// 판정하는 게임 코드(system)에서 사용하는 API라서, 그런 system이 없는 지금은 서버 안에서 사용하지 않는다. (dead_code 경고)
+#[derive(SystemParam)]
+pub struct LagCompensation<'w, 's> {
+    tick: Res<'w, ServerTick>,
+    histories: Query<'w, 's, (Entity, &'static TransformHistory)>,
+    view_ticks: Query<'w, 's, &'static ViewTick>,
+}
+
+impl LagCompensation<'_, '_> {
+    /// event를 보낸 클라이언트가 마지막 입력을 보낼 때 보고 있던 tick, 허용 범위 안으로 자른다.
+    /// 아직 입력을 받지 못했으면 현재 tick
+    pub fn rewind_tick_for<T>(&self, event: &FromClient<T>) -> f32 {
+        let view_tick = self.view_ticks.get(event.client).map_or(self.tick.0 as f32, |view_tick| view_tick.0);
+        self.rewind_tick(view_tick)
+    }
+
+    /// 현재 tick 이후나 `MAX_REWIND_TICKS`보다 이전은 허용하지 않는다.
+    pub fn rewind_tick(&self, view_tick: f32) -> f32 {
+        let current = self.tick.0 as f32;
+        view_tick.clamp(current - MAX_REWIND_TICKS as f32, current)
+    }
+
+    pub fn transform_at(&self, entity: Entity, tick: f32) -> Option<Transform> {
+        self.histories.get(entity).ok()?.1.at(tick)
+    }
+
+    /// 기록이 있는 모든 엔티티의 tick 시점 `Transform`
+    pub fn iter_at(&self, tick: f32) -> impl Iterator<Item = (Entity, Transform)> + '_ {
+        self.histories.iter().filter_map(move |(entity, history)| Some((entity, history.at(tick)?)))
+    }
+}
+
+// ----------------- system
+
+/// 입력이 여러 개 도착하면 가장 최신 tick의 입력을 사용한다.
+fn record_view_tick_system(mut commands: Commands, mut inputs: EventReader<FromClient<InputPacket>>, mut latest: Local<HashMap<Entity, (u32, f32)>>) {
+    latest.clear();
+    for input in inputs.read() {
+        let entry = latest.entry(input.client).or_insert((input.event.tick, input.event.view_tick));
+        if input.event.tick >= entry.0 {
+            *entry = (input.event.tick, input.event.view_tick);
+        }
+    }
+
+    for (client, (_, view_tick)) in latest.drain() {
+        // 연결이 끊겨서 엔티티가 이미 없을 수 있다.
+        commands.entity(client).try_insert(ViewTick(view_tick));
+    }
+}
+
+/// 고정 tick 시뮬레이션이 끝난 뒤 기록한다. 기록이 없는 복제 대상 엔티티에는 다음 tick부터 기록하도록 컴포넌트를 붙인다.
+fn record_history_system(
+    mut commands: Commands,
+    tick: Res<ServerTick>,
+    mut histories: Query<(&Transform, &mut TransformHistory)>,
+    new_entities: Query<Entity, (With<Replicated>, Without<TransformHistory>)>,
+) {
+    for (transform, mut history) in histories.iter_mut() {
+        history.0.push_back((tick.0, *transform));
+        while history.0.len() > MAX_REWIND_TICKS as usize + 1 {
+            history.0.pop_front();
+        }
+    }
+
+    for entity in new_entities.iter() {
+        commands.entity(entity).insert(TransformHistory::default());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    fn history(ticks: impl IntoIterator<Item = u32>) -> TransformHistory {
+        TransformHistory(ticks.into_iter().map(|tick| (tick, Transform::from_xyz(tick as f32 * 10.0, 0.0, 0.0))).collect())
+    }
+
+    #[test]
+    fn interpolates_between_recorded_ticks() {
+        let history = history(10..=12);
+        assert_eq!(history.at(11.0).unwrap().translation.x, 110.0);
+        assert_eq!(history.at(11.5).unwrap().translation.x, 115.0);
+        // 기록 범위 밖은 가장 가까운 기록
+        assert_eq!(history.at(5.0).unwrap().translation.x, 100.0);
+        assert_eq!(history.at(20.0).unwrap().translation.x, 120.0);
+    }
+
+    #[test]
+    fn rewind_is_limited_to_recorded_range() {
+        let mut world = World::new();
+        world.insert_resource(ServerTick(100));
+        let entity = world.spawn(history(100 - MAX_REWIND_TICKS..=100)).id();
+
+        let (rewound, transform) = world.run_system_once(move |lag_compensation: LagCompensation| {
+            let tick = lag_compensation.rewind_tick(0.0);
+            (tick, lag_compensation.transform_at(entity, tick))
+        }).unwrap();
+
+        assert_eq!(rewound, (100 - MAX_REWIND_TICKS) as f32);
+        assert_eq!(transform.unwrap().translation.x, rewound * 10.0);
+    }
+
+    #[test]
+    fn rewind_tick_for_uses_sender_view_tick() {
+        let mut world = World::new();
+        world.insert_resource(ServerTick(100));
+        let client = world.spawn(ViewTick(95.5)).id();
+        let other = world.spawn_empty().id();
+
+        let event = |client| FromClient { client, uuid: uuid::Uuid::nil(), event: () };
+        let (sender, unknown) = world.run_system_once(move |lag_compensation: LagCompensation| {
+            (lag_compensation.rewind_tick_for(&event(client)), lag_compensation.rewind_tick_for(&event(other)))
+        }).unwrap();
+
+        assert_eq!(sender, 95.5);
+        assert_eq!(unknown, 100.0);
+    }
+}
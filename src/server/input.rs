@@ -0,0 +1,136 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+
+use crate::common::{InputPacket, PlayerInput, TICK_RATE};
+
+use super::{event::{ClientEventAppExt, FromClient}, Client, SimulationSet};
+
+/// 처음 시작할 때와 최소로 유지하는 입력 지연 (tick)
+const MIN_INPUT_DELAY: u32 = 1;
+/// 입력 지연 최댓값, 이보다 느리게 도착하는 입력은 마지막 입력을 반복해서 사용한다.
+const MAX_INPUT_DELAY: u32 = 8;
+/// 다음에 사용할 tick보다 이만큼 앞선 입력은 받지 않는다.
+const MAX_BUFFERED_INPUTS: u32 = 64;
+/// 목표 지연보다 많이 쌓인 상태가 이 tick 수만큼 이어지면 지연을 줄인다. (1초)
+const SURPLUS_TICKS: u32 = TICK_RATE as u32;
+
+/// 클라이언트 입력 버퍼
+/// 입력은 네트워크 상태에 따라 몰려서 도착하므로 클라이언트 tick을 key로 버퍼에 넣어두고
+/// 서버 고정 tick마다 정확히 하나씩 꺼내서 `PlayerInput`에 넣는다.
+/// 버퍼에 넣는 것도 고정 tick에서 하므로 입력이 몇 번째 tick에 처리되는지는 프레임 시간에 영향을 받지 않는다.
+///
+/// ## 지연 조절
+/// - 꺼낼 입력이 아직 도착하지 않았으면 마지막 입력을 한 번 더 사용하고 기다린다. (목표 지연 1 tick 증가)
+/// - 목표 지연보다 2개 이상 더 쌓인 상태가 `SURPLUS_TICKS`동안 이어지면 입력 하나를 건너뛴다. (지연 1 tick 감소)
+/// - 중간 입력 하나만 유실되었으면 마지막 입력을 반복하고 넘어간다.
+pub struct InputPlugin;
+
+impl Plugin for InputPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_client_event::<InputPacket>()
+            .add_systems(FixedUpdate, (receive_input_system, consume_input_system).chain().in_set(SimulationSet::Input));
+    }
+}
+
+// ----------------- component
+
+#[derive(Component)]
+pub struct InputBuffer {
+    inputs: BTreeMap<u32, PlayerInput>,
+    /// 다음 고정 tick에 사용할 클라이언트 tick, 첫 입력을 받기 전에는 `None`
+    next_tick: Option<u32>,
+    /// 목표 지연 (tick)
+    delay: u32,
+    surplus_ticks: u32,
+    /// 입력이 도착하지 않아서 기다리는 중, 기다리는 동안 지연을 계속 늘리지 않도록 한 번만 늘린다.
+    starving: bool,
+}
+
+impl Default for InputBuffer {
+    fn default() -> Self {
+        InputBuffer { inputs: BTreeMap::new(), next_tick: None, delay: MIN_INPUT_DELAY, surplus_ticks: 0, starving: false }
+    }
+}
+
+impl InputBuffer {
+    /// 중복된 입력과 이미 사용한 tick의 입력은 무시한다.
+    /// 클라이언트가 시간 동기화로 tick을 크게 옮기면 버퍼를 비우고 새 tick에 맞춘다.
+    fn insert(&mut self, packet: &InputPacket) {
+        let jumped = self.next_tick.is_some_and(|next_tick| packet.tick.abs_diff(next_tick) >= MAX_BUFFERED_INPUTS);
+        if jumped {
+            self.inputs.clear();
+            self.next_tick = None;
+        }
+        let next_tick = *self.next_tick.get_or_insert(packet.tick.saturating_sub(self.delay));
+
+        for (age, input) in packet.inputs.iter().enumerate() {
+            let Some(tick) = packet.tick.checked_sub(age as u32) else {
+                break;
+            };
+            if tick < next_tick || tick >= next_tick + MAX_BUFFERED_INPUTS {
+                continue;
+            }
+            self.inputs.entry(tick).or_insert(*input);
+        }
+    }
+
+    /// 이번 tick에 사용할 입력, `None`이면 마지막 입력을 그대로 사용한다.
+    fn next(&mut self) -> Option<PlayerInput> {
+        let next_tick = self.next_tick?;
+        let buffered = self.inputs.len() as u32;
+
+        if buffered == 0 {
+            // 아직 도착하지 않음, cursor를 그대로 두면 실제 지연이 1 tick 늘어난다.
+            if !self.starving {
+                self.delay = (self.delay + 1).min(MAX_INPUT_DELAY);
+                self.starving = true;
+            }
+            self.surplus_ticks = 0;
+            return None;
+        }
+        self.starving = false;
+
+        let mut next_tick = next_tick;
+        if buffered > self.delay + 1 {
+            self.surplus_ticks += 1;
+            if self.surplus_ticks >= SURPLUS_TICKS {
+                // 너무 많이 쌓여 있으면 하나를 건너뛰어 지연을 줄인다.
+                self.inputs.remove(&next_tick);
+                next_tick += 1;
+                self.delay = self.delay.saturating_sub(1).max(MIN_INPUT_DELAY);
+                self.surplus_ticks = 0;
+            }
+        } else {
+            self.surplus_ticks = 0;
+        }
+
+        // 중간 입력이 유실되었으면 None
+        let input = self.inputs.remove(&next_tick);
+        self.next_tick = Some(next_tick + 1);
+        input
+    }
+}
+
+// ----------------- system
+
+fn receive_input_system(mut packets: EventReader<FromClient<InputPacket>>, mut query: Query<&mut InputBuffer, With<Client>>) {
+    for packet in packets.read() {
+        match query.get_mut(packet.client) {
+            Ok(mut buffer) => buffer.insert(&packet.event),
+            Err(_) => eprintln!("[input] input from non-player entity, uuid: {}", packet.uuid),
+        }
+    }
+}
+
+fn consume_input_system(mut query: Query<(&Client, &mut InputBuffer, &mut PlayerInput)>) {
+    for (client, mut buffer, mut player_input) in query.iter_mut() {
+        let delay = buffer.delay;
+        if let Some(input) = buffer.next() {
+            player_input.set_if_neq(input);
+        }
+        if buffer.delay != delay {
+            println!("[input] input delay changed, uuid: {}, delay: {} -> {}", client.0, delay, buffer.delay);
+        }
+    }
+}
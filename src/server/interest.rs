@@ -0,0 +1,312 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+
+use crate::common::ServerMessage;
+
+use super::{priority::ReplicationQueue, Client, ReplicationSet};
+
+/// 관심 영역(Area of Interest) 관리
+/// 모든 엔티티의 `Transform`을 모든 클라이언트에게 보내면 플레이어 수의 제곱으로 트래픽이 늘어나므로
+/// 클라이언트 주변의 엔티티만 골라서 spawn / update / despawn 메시지를 보내준다.
+pub struct InterestPlugin;
+
+impl Plugin for InterestPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(InterestSettings::default())
+            .insert_resource(SpatialGrid::default())
+            .add_systems(PostUpdate, (
+                update_spatial_grid_system,
+                update_interest_system,
+            ).chain().in_set(ReplicationSet::Interest));
+    }
+}
+
+// ----------------- component
+
+/// 네트워크로 복제되는 엔티티 표시
+/// `ChildOf`로 다른 복제 대상 엔티티에 붙은 엔티티는 자신의 위치와 상관없이 최상위 부모가 보이는 클라이언트에게 보인다.
+/// 자식에 `NetworkVisibility`가 있으면 부모가 보이는 클라이언트 중 규칙을 통과한 클라이언트에게만 보인다.
+#[derive(Component)]
+pub struct Replicated;
+
+/// 게임 코드에서 복제 대상 엔티티의 가시성을 직접 지정할 때 사용한다.
+/// 컴포넌트가 없으면 관심 영역 반경 안에 있는 클라이언트에게만 보인다.
+/// 게임 코드에서 붙이는 컴포넌트라서 서버 안에서는 만들지 않는다. (variant를 만들지 않는다는 dead_code 경고가 남는다)
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NetworkVisibility {
+    /// 거리와 상관없이 모든 클라이언트에게 보인다.
+    Always,
+    /// 어떤 클라이언트에게도 보내지 않는다.
+    Never,
+    /// `NetworkOwner`로 지정된 클라이언트에게만 보인다.
+    OwnerOnly,
+    /// 같은 `Team`에 속한 클라이언트에게만 보인다.
+    Team,
+}
+
+impl NetworkVisibility {
+    /// 규칙이 `client`에게 엔티티를 보여주는지, 엔티티의 `NetworkOwner`와 `Team`으로 판단한다.
+    fn allows(self, client: Entity, client_team: Option<&Team>, owner: Option<&NetworkOwner>, team: Option<&Team>) -> bool {
+        match self {
+            NetworkVisibility::Always => true,
+            NetworkVisibility::Never => false,
+            NetworkVisibility::OwnerOnly => owner.is_some_and(|owner| owner.0 == client),
+            NetworkVisibility::Team => team.is_some() && team == client_team,
+        }
+    }
+}
+
+/// 엔티티를 소유한 클라이언트 엔티티
+#[derive(Component)]
+pub struct NetworkOwner(pub Entity);
+
+/// 팀 번호, 클라이언트 엔티티와 복제 대상 엔티티 모두에 붙일 수 있다.
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub struct Team(pub u32);
+
+/// 클라이언트가 현재 알고 있는(spawn 메시지를 받은) 엔티티 목록
+#[derive(Component, Default)]
+pub struct ClientVisibility(pub HashSet<Entity>);
+
+// ----------------- resource
+
+#[derive(Resource)]
+pub struct InterestSettings {
+    /// 공간 격자 한 칸의 크기
+    pub cell_size: f32,
+    /// 클라이언트 위치를 중심으로 한 관심 영역 반경
+    pub radius: f32,
+}
+
+impl Default for InterestSettings {
+    fn default() -> Self {
+        InterestSettings { cell_size: 250.0, radius: 750.0 }
+    }
+}
+
+/// 매 프레임 복제 대상 엔티티의 위치로 다시 만들어지는 공간 격자
+/// `NetworkVisibility`가 지정된 엔티티는 거리와 무관하게 판단하므로 격자에 넣지 않고 따로 모아둔다.
+#[derive(Resource, Default)]
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<IVec2, Vec<Entity>>,
+    overrides: Vec<Entity>,
+}
+
+impl SpatialGrid {
+    fn cell_of(&self, position: Vec3) -> IVec2 {
+        (position.truncate() / self.cell_size).floor().as_ivec2()
+    }
+
+    /// 반경 안에 걸치는 격자 칸의 엔티티를 돌려준다. 실제 거리 검사는 호출하는 쪽에서 한다.
+    fn query(&self, center: Vec3, radius: f32) -> impl Iterator<Item = Entity> + '_ {
+        let min = self.cell_of(center - Vec3::splat(radius));
+        let max = self.cell_of(center + Vec3::splat(radius));
+
+        (min.x..=max.x)
+            .flat_map(move |x| (min.y..=max.y).map(move |y| IVec2::new(x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+    }
+}
+
+// ----------------- system
+
+fn update_spatial_grid_system(
+    settings: Res<InterestSettings>,
+    mut grid: ResMut<SpatialGrid>,
+    query: Query<(Entity, &Transform, Has<NetworkVisibility>), (With<Replicated>, Without<ChildOf>)>,
+) {
+    grid.cell_size = settings.cell_size;
+    grid.cells.clear();
+    grid.overrides.clear();
+
+    // 자식 엔티티의 Transform은 부모 기준이므로 격자에 넣지 않는다.
+    for (entity, transform, has_visibility) in query.iter() {
+        if has_visibility {
+            grid.overrides.push(entity);
+        } else {
+            let cell = grid.cell_of(transform.translation);
+            grid.cells.entry(cell).or_default().push(entity);
+        }
+    }
+}
+
+/// 클라이언트마다 관심 대상 엔티티를 다시 계산하고
+/// 이전 목록과 비교해서 새로 들어온 엔티티는 spawn, 빠진 엔티티는 despawn 메시지를 대기열에 넣고,
+/// 계속 보이는 엔티티 중 `Transform`이 바뀐 엔티티는 update 대기 목록에 넣는다.
+/// 실제 전송은 `priority`의 대역폭 예산에 맞춰서 이루어진다.
+/// 보이는 엔티티의 복제 대상 자식도 같이 보이며, spawn 메시지는 부모가 먼저 가도록 깊이 순서로 보낸다.
+fn update_interest_system(
+    settings: Res<InterestSettings>,
+    grid: Res<SpatialGrid>,
+    replicated: Query<(Ref<Transform>, Option<&NetworkVisibility>, Option<&NetworkOwner>, Option<&Team>), With<Replicated>>,
+    hierarchy: Query<(Option<&ChildOf>, Option<&Children>), With<Replicated>>,
+    mut clients: Query<(Entity, &Transform, Option<&Team>, &mut ClientVisibility, &mut ReplicationQueue), With<Client>>,
+) {
+    let radius_squared = settings.radius * settings.radius;
+
+    for (client_entity, client_transform, client_team, mut visibility, mut queue) in clients.iter_mut() {
+        let center = client_transform.translation;
+
+        let mut relevant: HashSet<Entity> = grid.query(center, settings.radius)
+            .chain(grid.overrides.iter().copied())
+            .filter(|entity| {
+                let Ok((transform, rule, owner, team)) = replicated.get(*entity) else {
+                    return false;
+                };
+
+                match rule {
+                    None => transform.translation.distance_squared(center) <= radius_squared,
+                    Some(rule) => rule.allows(client_entity, client_team, owner, team),
+                }
+            })
+            .collect();
+
+        // 자식은 거리와 상관없이 부모를 따라가지만 자신의 규칙은 따로 확인한다. 보이지 않는 자식의 자식도 보내지 않는다.
+        let mut stack: Vec<Entity> = relevant.iter().copied().collect();
+        while let Some(entity) = stack.pop() {
+            let Ok((_, Some(children))) = hierarchy.get(entity) else {
+                continue;
+            };
+            for child in children.iter() {
+                let Ok((_, rule, owner, team)) = replicated.get(child) else {
+                    continue;
+                };
+                if rule.is_some_and(|rule| !rule.allows(client_entity, client_team, owner, team)) {
+                    continue;
+                }
+                if relevant.insert(child) {
+                    stack.push(child);
+                }
+            }
+        }
+
+        for entity in visibility.0.difference(&relevant) {
+            queue.pending.remove(entity);
+            queue.sent.remove(entity);
+            queue.messages.push(ServerMessage::EntityDespawn { entity: entity.to_bits() });
+        }
+
+        let mut spawned: Vec<(usize, Entity)> = Vec::new();
+        for entity in relevant.iter() {
+            let Ok((transform, ..)) = replicated.get(*entity) else {
+                continue;
+            };
+
+            if !visibility.0.contains(entity) {
+                spawned.push((hierarchy_depth(*entity, &hierarchy), *entity));
+            } else if transform.is_changed() {
+                // 보내지 못하고 남아있는 경우 누적된 우선순위를 유지한다.
+                queue.pending.entry(*entity).or_insert(0.0);
+            }
+        }
+
+        spawned.sort_by_key(|(depth, _)| *depth);
+        for (_, entity) in spawned {
+            let Ok((transform, ..)) = replicated.get(entity) else {
+                continue;
+            };
+            let parent = hierarchy.get(entity).ok()
+                .and_then(|(child_of, _)| child_of)
+                .map(|child_of| child_of.parent())
+                .filter(|parent| relevant.contains(parent));
+
+            // spawn 메시지에 현재 Transform이 모두 포함되므로 대기 중인 update는 필요 없다.
+            queue.pending.remove(&entity);
+            queue.sent.insert(entity, *transform);
+            queue.messages.push(ServerMessage::EntitySpawn {
+                entity: entity.to_bits(),
+                translation: transform.translation,
+                rotation: transform.rotation,
+                scale: transform.scale,
+                parent: parent.map(|parent| parent.to_bits()),
+            });
+        }
+
+        visibility.0 = relevant;
+    }
+}
+
+/// 복제 대상 부모를 따라 올라간 깊이, 최상위 엔티티는 0
+fn hierarchy_depth(entity: Entity, hierarchy: &Query<(Option<&ChildOf>, Option<&Children>), With<Replicated>>) -> usize {
+    let mut depth = 0;
+    let mut current = entity;
+    while let Ok((Some(child_of), _)) = hierarchy.get(current) {
+        current = child_of.parent();
+        depth += 1;
+    }
+    depth
+}
+
+/// 복제 대상 부모를 따라 올라간 최상위 엔티티, 자식의 거리 계산에 사용한다.
+pub fn hierarchy_root(entity: Entity, parents: &Query<&ChildOf, With<Replicated>>) -> Entity {
+    let mut current = entity;
+    while let Ok(child_of) = parents.get(current) {
+        current = child_of.parent();
+    }
+    current
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    /// 반경 밖의 엔티티도 `NetworkVisibility` 규칙에 따라 보인다.
+    #[test]
+    fn network_visibility_overrides_distance() {
+        let mut app = App::new();
+        app.add_plugins(InterestPlugin);
+
+        let spawn_client = |app: &mut App, index: u128, team: u32| {
+            app.world_mut().spawn((Client(Uuid::from_u128(index)), Transform::default(), Team(team), ClientVisibility::default(), ReplicationQueue::default())).id()
+        };
+        let first = spawn_client(&mut app, 1, 0);
+        let second = spawn_client(&mut app, 2, 1);
+
+        let far = Transform::from_xyz(10_000.0, 0.0, 0.0);
+        let world = app.world_mut();
+        let hidden = world.spawn((Replicated, far)).id();
+        let nearby = world.spawn((Replicated, Transform::default())).id();
+        let always = world.spawn((Replicated, far, NetworkVisibility::Always)).id();
+        let never = world.spawn((Replicated, Transform::default(), NetworkVisibility::Never)).id();
+        let owned = world.spawn((Replicated, far, NetworkVisibility::OwnerOnly, NetworkOwner(first))).id();
+        let team = world.spawn((Replicated, far, NetworkVisibility::Team, Team(1))).id();
+
+        app.update();
+
+        let visible = |client: Entity| app.world().get::<ClientVisibility>(client).unwrap().0.clone();
+        assert_eq!(visible(first), HashSet::from([nearby, always, owned]));
+        assert_eq!(visible(second), HashSet::from([nearby, always, team]));
+        assert!(!visible(first).contains(&hidden) && !visible(second).contains(&never));
+    }
+
+    /// 보이는 부모의 자식도 자신의 `NetworkVisibility` 규칙을 따른다.
+    #[test]
+    fn child_visibility_rules_apply() {
+        let mut app = App::new();
+        app.add_plugins(InterestPlugin);
+
+        let world = app.world_mut();
+        let first = world.spawn((Client(Uuid::from_u128(1)), Transform::default(), Team(0), ClientVisibility::default(), ReplicationQueue::default())).id();
+        let second = world.spawn((Client(Uuid::from_u128(2)), Transform::default(), Team(1), ClientVisibility::default(), ReplicationQueue::default())).id();
+
+        let parent = world.spawn((Replicated, Transform::default())).id();
+        let inherited = world.spawn((Replicated, Transform::default(), ChildOf(parent))).id();
+        let never = world.spawn((Replicated, Transform::default(), NetworkVisibility::Never, ChildOf(parent))).id();
+        let under_never = world.spawn((Replicated, Transform::default(), ChildOf(never))).id();
+        let owned = world.spawn((Replicated, Transform::default(), NetworkVisibility::OwnerOnly, NetworkOwner(first), ChildOf(parent))).id();
+        let team = world.spawn((Replicated, Transform::default(), NetworkVisibility::Team, Team(1), ChildOf(parent))).id();
+
+        app.update();
+
+        let visible = |client: Entity| app.world().get::<ClientVisibility>(client).unwrap().0.clone();
+        assert_eq!(visible(first), HashSet::from([parent, inherited, owned]));
+        assert_eq!(visible(second), HashSet::from([parent, inherited, team]));
+        assert!(!visible(first).contains(&under_never) && !visible(second).contains(&never));
+    }
+}
@@ -0,0 +1,35 @@
+use bevy::prelude::*;
+
+use crate::common::{PlayerInput, PLAYER_SPEED, TICK_RATE};
+
+use super::SimulationSet;
+
+/// 플레이어 이동
+/// 고정 tick(`FixedUpdate`)마다 입력 버퍼에서 꺼낸 `PlayerInput` 방향으로 속도를 정해서 위치를 적분한다.
+pub struct MovementPlugin;
+
+impl Plugin for MovementPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Time::<Fixed>::from_hz(TICK_RATE))
+            .add_systems(FixedUpdate, move_player_system.in_set(SimulationSet::Movement));
+    }
+}
+
+// ----------------- component
+
+/// 초당 이동 거리
+#[derive(Component, Default, Clone, Copy, Debug)]
+pub struct Velocity(pub Vec2);
+
+// ----------------- system
+
+/// 변경된 위치는 관심 영역 시스템(`interest`)이 주변 클라이언트들에게 보내준다.
+/// 멈춰있는 플레이어는 `Transform`을 건드리지 않아서 update 메시지를 보내지 않는다.
+fn move_player_system(time: Res<Time>, mut query: Query<(&PlayerInput, &mut Velocity, &mut Transform)>) {
+    for (input, mut velocity, mut transform) in query.iter_mut() {
+        velocity.0 = input.axis() * PLAYER_SPEED;
+        if velocity.0 != Vec2::ZERO {
+            transform.translation += (velocity.0 * time.delta_secs()).extend(0.0);
+        }
+    }
+}
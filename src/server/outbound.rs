@@ -0,0 +1,200 @@
+use std::{collections::{HashMap, VecDeque}, sync::{Arc, Mutex}};
+
+use tokio::sync::Notify;
+
+use crate::common::{ChannelKind, Outgoing, ServerMessage};
+
+/// 연결마다 쌓아둘 수 있는 최대 송신 메시지 수
+pub const OUTBOUND_QUEUE_CAPACITY: usize = 1024;
+
+/// 연결마다 하나씩 있는 송신 대기열 (bevy system -> writer task)
+///
+/// bevy system은 `OutboundSender::push`로 기다리지 않고 메시지를 넣고,
+/// 연결마다 하나뿐인 writer task가 `OutboundReceiver::recv`로 쌓인 메시지를 한 번에 꺼내 넣은 순서대로 보낸다.
+/// 메시지는 `ServerMessage` 그대로 쌓아두고 직렬화는 writer task에서 한다. (lock을 잡는 시간을 줄이고 합칠 때 다시 풀지 않는다)
+///
+/// ## backpressure 정책
+/// - coalesce: `UnreliableSequenced` 메시지는 같은 key를 가진 아직 보내지 않은 메시지와 `ServerMessage::coalesce`로 합쳐서 그 자리에서 대체한다.
+///   key별 위치를 기억해두므로 대기열을 훑지 않는다.
+/// - drop-oldest: 대기열이 가득 차면 가장 오래된 `UnreliableSequenced` 메시지를 버린다. 버릴 메시지가 없으면 새 메시지를 버린다.
+///   버려진 필드는 `priority`의 주기적인 전체 상태 update로 복구된다.
+/// - disconnect: reliable 메시지를 넣을 자리가 없으면 대기열을 닫고, writer task는 연결을 끊는다.
+pub fn outbound_queue(capacity: usize) -> (OutboundSender, OutboundReceiver) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(QueueState { messages: VecDeque::new(), keys: HashMap::new(), capacity, closed: false, overflowed: false }),
+        notify: Notify::new(),
+    });
+
+    (OutboundSender(shared.clone()), OutboundReceiver(shared))
+}
+
+struct Shared {
+    state: Mutex<QueueState>,
+    notify: Notify,
+}
+
+struct QueueState {
+    messages: VecDeque<Outgoing<ServerMessage>>,
+    /// 아직 보내지 않은 `UnreliableSequenced` 메시지의 key별 `messages` 위치
+    keys: HashMap<u64, usize>,
+    capacity: usize,
+    closed: bool,
+    overflowed: bool,
+}
+
+#[derive(Debug)]
+pub enum OutboundError {
+    /// 연결이 이미 끊어짐
+    Closed,
+    /// reliable 메시지를 넣을 자리가 없어서 대기열을 닫음
+    Overflow,
+}
+
+/// bevy 쪽에서 사용하는 송신 대기열, `ClientSender` 컴포넌트가 소유하며 drop되면 writer task도 끝난다.
+pub struct OutboundSender(Arc<Shared>);
+
+/// writer task 쪽에서 사용하는 송신 대기열
+pub struct OutboundReceiver(Arc<Shared>);
+
+impl OutboundSender {
+    pub fn push(&self, outgoing: Outgoing<ServerMessage>) -> Result<(), OutboundError> {
+        let mut state = self.0.state.lock().unwrap();
+        if state.closed {
+            return Err(OutboundError::Closed);
+        }
+
+        let unreliable = outgoing.channel == ChannelKind::UnreliableSequenced;
+
+        // coalesce
+        // 바뀐 필드만 담은 update를 그대로 대체하면 이전 메시지에만 있던 필드가 전달되지 않으므로 합친 뒤 새 메시지의 순서 번호를 사용한다.
+        let key = outgoing.key.filter(|_| unreliable);
+        if let Some(index) = key.and_then(|key| state.keys.get(&key).copied()) {
+            let pending = &mut state.messages[index];
+            pending.message.coalesce(outgoing.message);
+            pending.seq = outgoing.seq;
+            return Ok(());
+        }
+
+        if state.messages.len() >= state.capacity {
+            // 대기열이 가득 찼을 때만 훑는다.
+            let oldest_unreliable = state.messages.iter().position(|pending| pending.channel == ChannelKind::UnreliableSequenced);
+            match (oldest_unreliable, unreliable) {
+                // drop-oldest
+                (Some(index), _) => {
+                    state.remove(index);
+                },
+                (None, true) => return Ok(()),
+                // disconnect
+                (None, false) => {
+                    state.closed = true;
+                    state.overflowed = true;
+                    drop(state);
+                    self.0.notify.notify_one();
+                    return Err(OutboundError::Overflow);
+                },
+            }
+        }
+
+        if let Some(key) = key {
+            let index = state.messages.len();
+            state.keys.insert(key, index);
+        }
+        state.messages.push_back(outgoing);
+        drop(state);
+        self.0.notify.notify_one();
+
+        Ok(())
+    }
+}
+
+impl QueueState {
+    /// 중간의 메시지를 버리고 뒤에 있던 메시지의 위치를 당긴다.
+    fn remove(&mut self, index: usize) {
+        let Some(removed) = self.messages.remove(index) else {
+            return;
+        };
+        if let Some(key) = removed.key.filter(|key| self.keys.get(key) == Some(&index)) {
+            self.keys.remove(&key);
+        }
+        for position in self.keys.values_mut() {
+            if *position > index {
+                *position -= 1;
+            }
+        }
+    }
+}
+
+impl Drop for OutboundSender {
+    fn drop(&mut self) {
+        self.0.state.lock().unwrap().closed = true;
+        self.0.notify.notify_one();
+    }
+}
+
+impl OutboundReceiver {
+    /// 쌓인 메시지를 모두 꺼낸다. 대기열이 닫혔으면 `None`
+    pub async fn recv(&mut self) -> Option<Vec<Outgoing<ServerMessage>>> {
+        loop {
+            {
+                let mut state = self.0.state.lock().unwrap();
+                if state.overflowed {
+                    return None;
+                }
+                if !state.messages.is_empty() {
+                    state.keys.clear();
+                    return Some(state.messages.drain(..).collect());
+                }
+                if state.closed {
+                    return None;
+                }
+            }
+            self.0.notify.notified().await;
+        }
+    }
+
+    /// 대기열이 넘쳐서 닫혔는지
+    pub fn overflowed(&self) -> bool {
+        self.0.state.lock().unwrap().overflowed
+    }
+}
+
+impl Drop for OutboundReceiver {
+    fn drop(&mut self) {
+        self.0.state.lock().unwrap().closed = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{Quat, Vec3};
+    use futures_util::FutureExt;
+
+    use super::*;
+
+    fn update(entity: u64, seq: u32, translation: Option<Vec3>, rotation: Option<Quat>) -> Outgoing<ServerMessage> {
+        let message = ServerMessage::PlayerUpdate { entity, translation, rotation, scale: None };
+        Outgoing { channel: ChannelKind::UnreliableSequenced, seq, key: message.supersede_key(), message }
+    }
+
+    /// 같은 key의 update는 필드를 합치고, 가득 차서 중간 메시지를 버린 뒤에도 key별 위치가 맞아야 한다.
+    #[test]
+    fn coalesce_keeps_fields_and_positions() {
+        let (sender, mut receiver) = outbound_queue(3);
+        sender.push(update(1, 0, Some(Vec3::X), None)).unwrap();
+        sender.push(update(2, 1, Some(Vec3::Y), None)).unwrap();
+        sender.push(update(1, 2, None, Some(Quat::IDENTITY))).unwrap();
+        let despawn = ServerMessage::EntityDespawn { entity: 9 };
+        sender.push(Outgoing { channel: ChannelKind::ReliableOrdered, seq: 0, key: None, message: despawn }).unwrap();
+        // 가득 찼으므로 가장 오래된 entity 1 update를 버린다.
+        sender.push(update(3, 3, Some(Vec3::Z), None)).unwrap();
+        sender.push(update(2, 4, None, Some(Quat::IDENTITY))).unwrap();
+
+        let pending = receiver.recv().now_or_never().flatten().unwrap();
+        let summary: Vec<(Option<u64>, u32)> = pending.iter().map(|outgoing| (outgoing.key, outgoing.seq)).collect();
+        assert_eq!(summary, vec![(Some(2), 4), (None, 0), (Some(3), 3)]);
+        let ServerMessage::PlayerUpdate { translation, rotation, .. } = &pending[0].message else {
+            panic!("expected player update");
+        };
+        assert_eq!((*translation, *rotation), (Some(Vec3::Y), Some(Quat::IDENTITY)));
+    }
+}
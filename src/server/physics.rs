@@ -0,0 +1,162 @@
+use bevy::prelude::*;
+
+use crate::common::{ARENA_WALLS, WORLD_HALF_EXTENTS};
+
+use super::{Client, SimulationSet};
+
+/// 한 tick에 겹침을 푸는 반복 횟수, 여러 플레이어가 뭉쳐 있을 때 한 번에 다 풀리지 않는다.
+const SOLVER_ITERATIONS: usize = 4;
+
+/// 간단한 2D 물리
+/// 고정 tick마다 이동이 끝난 뒤 `Collider`끼리 겹친 만큼 밀어내고, 벽(`StaticBody`)과 월드 경계(`WorldBounds`) 밖으로 나가지 않게 한다.
+/// 항상 같은 순서로 풀기 때문에 같은 위치에서는 같은 결과가 나오고, 바뀐 위치는 이동과 똑같이 복제된다.
+pub struct PhysicsPlugin;
+
+impl Plugin for PhysicsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(WorldBounds { min: -WORLD_HALF_EXTENTS, max: WORLD_HALF_EXTENTS })
+            .add_systems(Startup, spawn_walls_system)
+            .add_systems(FixedUpdate, resolve_collision_system.in_set(SimulationSet::Collision));
+    }
+}
+
+// ----------------- component
+
+/// 충돌 모양, `Transform`의 x, y 위치가 중심
+#[derive(Component, Clone, Copy, Debug)]
+pub enum Collider {
+    Circle { radius: f32 },
+    Aabb { half_extents: Vec2 },
+}
+
+impl Collider {
+    /// 중심에서 각 축 방향 끝까지의 거리
+    fn half_extents(&self) -> Vec2 {
+        match *self {
+            Collider::Circle { radius } => Vec2::splat(radius),
+            Collider::Aabb { half_extents } => half_extents,
+        }
+    }
+}
+
+/// 움직이지 않는 충돌체 (벽), 다른 충돌체에 밀리지 않는다.
+#[derive(Component)]
+pub struct StaticBody;
+
+// ----------------- resource
+
+/// 충돌체가 나갈 수 없는 월드 영역
+#[derive(Resource, Clone, Copy)]
+pub struct WorldBounds {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl WorldBounds {
+    fn clamp(&self, collider: &Collider, position: Vec2) -> Vec2 {
+        let half_extents = collider.half_extents();
+        position.clamp(self.min + half_extents, self.max - half_extents)
+    }
+}
+
+/// a를 b 밖으로 밀어내는 최소 이동량, 겹치지 않으면 `None`
+/// 중심이 완전히 같으면 +x 방향으로 밀어낸다.
+fn penetration(a: (Vec2, &Collider), b: (Vec2, &Collider)) -> Option<Vec2> {
+    match (a.1, b.1) {
+        (Collider::Circle { radius: ra }, Collider::Circle { radius: rb }) => {
+            let delta = a.0 - b.0;
+            let distance = delta.length();
+            let overlap = ra + rb - distance;
+            (overlap > 0.0).then(|| delta.try_normalize().unwrap_or(Vec2::X) * overlap)
+        },
+        (Collider::Circle { radius }, Collider::Aabb { half_extents }) => {
+            let local = a.0 - b.0;
+            let closest = local.clamp(-*half_extents, *half_extents);
+            if closest != local {
+                // 원의 중심이 상자 밖: 가장 가까운 점에서 반지름만큼 떨어뜨린다.
+                let delta = local - closest;
+                let overlap = radius - delta.length();
+                (overlap > 0.0).then(|| delta.normalize() * overlap)
+            } else {
+                // 원의 중심이 상자 안: 덜 들어간 축으로 밀어낸다.
+                let depth = *half_extents - local.abs();
+                Some(if depth.x < depth.y {
+                    Vec2::new(local.x.signum() * (depth.x + radius), 0.0)
+                } else {
+                    Vec2::new(0.0, local.y.signum() * (depth.y + radius))
+                })
+            }
+        },
+        (Collider::Aabb { .. }, Collider::Circle { .. }) => penetration(b, a).map(|push| -push),
+        (Collider::Aabb { half_extents: ha }, Collider::Aabb { half_extents: hb }) => {
+            let local = a.0 - b.0;
+            let depth = *ha + *hb - local.abs();
+            if depth.x <= 0.0 || depth.y <= 0.0 {
+                None
+            } else if depth.x < depth.y {
+                Some(Vec2::new(local.x.signum() * depth.x, 0.0))
+            } else {
+                Some(Vec2::new(0.0, local.y.signum() * depth.y))
+            }
+        },
+    }
+}
+
+// ----------------- system
+
+fn spawn_walls_system(mut commands: Commands) {
+    for (center, half_extents) in ARENA_WALLS {
+        commands.spawn((
+            Collider::Aabb { half_extents },
+            StaticBody,
+            Transform::from_translation(center.extend(0.0)),
+        ));
+    }
+}
+
+/// 움직이는 충돌체끼리는 겹친 만큼 반씩 밀어내고, 벽과 월드 경계는 마지막에 적용해서 항상 지켜지게 한다.
+/// 모든 쌍을 비교하므로 (O(n^2)) 충돌체가 많아지면 공간 격자가 필요하다.
+/// 위치가 실제로 바뀐 엔티티의 `Transform`만 수정해서 멈춰있는 플레이어는 update를 보내지 않는다.
+fn resolve_collision_system(
+    bounds: Res<WorldBounds>,
+    mut bodies: Query<(Entity, Option<&Client>, &Collider, &mut Transform), Without<StaticBody>>,
+    walls: Query<(&Collider, &Transform), With<StaticBody>>,
+) {
+    // query 순서는 보장되지 않으므로 클라이언트 id 순서(클라이언트가 아니면 엔티티 순서)로 정렬해서 항상 같은 순서로 푼다.
+    let mut positions: Vec<(Entity, Collider, Vec2)> = bodies.iter()
+        .sort_by_key::<(Option<&Client>, Entity), _>(|(client, entity)| (client.map(|client| client.0), *entity))
+        .map(|(entity, _, collider, transform)| (entity, *collider, transform.translation.truncate()))
+        .collect();
+    let walls: Vec<(Collider, Vec2)> = walls.iter()
+        .map(|(collider, transform)| (*collider, transform.translation.truncate()))
+        .collect();
+
+    for _ in 0..SOLVER_ITERATIONS {
+        for i in 0..positions.len() {
+            for j in (i + 1)..positions.len() {
+                let (_, a, a_position) = positions[i];
+                let (_, b, b_position) = positions[j];
+                if let Some(push) = penetration((a_position, &a), (b_position, &b)) {
+                    positions[i].2 += push / 2.0;
+                    positions[j].2 -= push / 2.0;
+                }
+            }
+        }
+
+        for (_, collider, position) in positions.iter_mut() {
+            for (wall, wall_position) in walls.iter() {
+                if let Some(push) = penetration((*position, collider), (*wall_position, wall)) {
+                    *position += push;
+                }
+            }
+            *position = bounds.clamp(collider, *position);
+        }
+    }
+
+    for (entity, _, position) in positions {
+        let (_, _, _, mut transform) = bodies.get_mut(entity).unwrap();
+        if transform.translation.truncate() != position {
+            transform.translation = position.extend(transform.translation.z);
+        }
+    }
+}
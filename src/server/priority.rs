@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::common::{quantize::{quantize_rotation, PositionQuantization, DEFAULT_POSITION_QUANTIZATION, DEFAULT_SCALE_QUANTIZATION}, ServerMessage};
+
+use super::{aggregate::OutboundBuffer, encoded_len, interest::{hierarchy_root, Replicated}, ReplicationSet};
+
+/// 클라이언트별 대역폭 예산과 update 우선순위
+/// 관심 영역 시스템이 `ReplicationQueue`에 쌓아둔 update를 우선순위 순으로 정렬하여 예산만큼만 `OutboundBuffer`에 넣고
+/// 보내지 못한 엔티티는 우선순위를 누적시켜 다음 tick에 먼저 보내지도록 한다.
+pub struct PriorityPlugin;
+
+impl Plugin for PriorityPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(BandwidthSettings::default())
+            .add_systems(PostUpdate, send_replication_system.in_set(ReplicationSet::Send));
+    }
+}
+
+// ----------------- component
+
+/// 복제 대상 엔티티의 기본 우선순위, 컴포넌트가 없으면 1.0
+#[derive(Component, Clone, Copy)]
+pub struct ReplicationPriority(pub f32);
+
+/// 클라이언트 연결마다 사용할 수 있는 초당 바이트
+#[derive(Component)]
+pub struct BandwidthBudget {
+    pub bytes_per_second: f32,
+}
+
+/// 클라이언트에게 보낼 복제 메시지 대기열
+#[derive(Component, Default)]
+pub struct ReplicationQueue {
+    /// spawn, despawn 처럼 예산과 상관없이 반드시 보내야 하는 메시지
+    pub messages: Vec<ServerMessage>,
+    /// 아직 보내지 못한 update 대상 엔티티와 누적된 우선순위
+    pub pending: HashMap<Entity, f32>,
+    /// 클라이언트에게 마지막으로 보낸 `Transform`, 바뀐 필드만 보낼 때 비교 대상
+    /// ack를 받지 않으므로 유실되거나 대기열에서 버려진 필드가 있을 수 있다.
+    pub sent: HashMap<Entity, Transform>,
+    /// 엔티티마다 마지막으로 모든 필드를 보낸 시간(초), `BandwidthSettings::full_update_interval`마다 다시 보낸다.
+    full_sent_at: HashMap<Entity, f32>,
+    /// 남은 바이트 (token bucket), 큰 메시지를 보내면 음수가 될 수 있다.
+    available_bytes: f32,
+}
+
+// ----------------- resource
+
+#[derive(Resource)]
+pub struct BandwidthSettings {
+    /// 새로 연결된 클라이언트의 `BandwidthBudget` 기본값
+    pub bytes_per_second: f32,
+    /// 한 번에 몰아서 보낼 수 있는 최대 시간(초), token bucket 크기 = bytes_per_second * burst_seconds
+    pub burst_seconds: f32,
+    /// 거리에 따른 우선순위 감소 기준 거리
+    pub distance_falloff: f32,
+    /// 보이는 엔티티마다 모든 필드를 담은 update를 다시 보내는 간격(초)
+    /// 유실된 update 때문에 클라이언트 상태가 계속 어긋나 있지 않도록 한다.
+    pub full_update_interval: f32,
+}
+
+impl Default for BandwidthSettings {
+    fn default() -> Self {
+        BandwidthSettings { bytes_per_second: 32.0 * 1024.0, burst_seconds: 0.25, distance_falloff: 250.0, full_update_interval: 1.0 }
+    }
+}
+
+impl BandwidthSettings {
+    pub fn budget(&self) -> BandwidthBudget {
+        BandwidthBudget { bytes_per_second: self.bytes_per_second }
+    }
+}
+
+// ----------------- system
+
+/// 1. 예산을 경과 시간만큼 채운다.
+/// 2. spawn, despawn 메시지는 모두 보낸다.
+/// 3. `full_update_interval`이 지난 엔티티는 바뀌지 않았어도 모든 필드를 담아 보내도록 update 대기 목록에 넣는다.
+/// 4. 남은 update를 (기본 우선순위 * 거리 가중치)만큼 누적시키고 누적값이 큰 순서대로 예산이 남아있는 동안 보낸다.
+///    자식 엔티티는 최상위 부모와의 거리를 사용한다.
+fn send_replication_system(
+    time: Res<Time>,
+    settings: Res<BandwidthSettings>,
+    replicated: Query<(&Transform, Option<&ReplicationPriority>)>,
+    parents: Query<&ChildOf, With<Replicated>>,
+    mut clients: Query<(&Transform, &BandwidthBudget, &mut OutboundBuffer, &mut ReplicationQueue)>,
+) {
+    for (client_transform, budget, mut buffer, mut queue) in clients.iter_mut() {
+        let queue = &mut *queue;
+        let max_bytes = budget.bytes_per_second * settings.burst_seconds;
+        queue.available_bytes = (queue.available_bytes + budget.bytes_per_second * time.delta_secs()).min(max_bytes);
+
+        for server_msg in queue.messages.drain(..) {
+            queue.available_bytes -= encoded_len(&server_msg) as f32;
+            buffer.push(server_msg);
+        }
+
+        // 이미 사라진 엔티티는 대기열에서 제거
+        queue.pending.retain(|entity, _| replicated.contains(*entity));
+        queue.sent.retain(|entity, _| replicated.contains(*entity));
+
+        // spawn 메시지에 모든 필드가 담기므로 처음 보낸 시간부터 센다.
+        let now = time.elapsed_secs();
+        let sent = &queue.sent;
+        queue.full_sent_at.retain(|entity, _| sent.contains_key(entity));
+        for entity in queue.sent.keys() {
+            let full_sent_at = *queue.full_sent_at.entry(*entity).or_insert(now);
+            if now - full_sent_at >= settings.full_update_interval {
+                queue.pending.entry(*entity).or_insert(0.0);
+            }
+        }
+
+        for (entity, accumulated) in queue.pending.iter_mut() {
+            let (transform, priority) = replicated.get(*entity).unwrap();
+            let position = replicated.get(hierarchy_root(*entity, &parents)).map_or(transform.translation, |(root, _)| root.translation);
+            let base = priority.map_or(1.0, |priority| priority.0);
+            let distance = position.distance(client_transform.translation);
+            *accumulated += base / (1.0 + distance / settings.distance_falloff);
+        }
+
+        let mut ranked: Vec<(Entity, f32)> = queue.pending.iter().map(|(entity, accumulated)| (*entity, *accumulated)).collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        for (entity, _) in ranked {
+            if queue.available_bytes <= 0.0 {
+                break;
+            }
+
+            queue.pending.remove(&entity);
+
+            let (transform, _) = replicated.get(entity).unwrap();
+            let full_update = queue.full_sent_at.get(&entity).is_none_or(|sent_at| now - sent_at >= settings.full_update_interval);
+            let baseline = if full_update { None } else { queue.sent.get(&entity) };
+            let Some(server_msg) = transform_update(entity, baseline, transform) else {
+                continue;
+            };
+            if full_update {
+                queue.full_sent_at.insert(entity, now);
+            }
+            queue.sent.insert(entity, *transform);
+            queue.available_bytes -= encoded_len(&server_msg) as f32;
+            buffer.push(server_msg);
+        }
+    }
+}
+
+/// 마지막으로 보낸 `Transform`과 비교해서 양자화한 값이 바뀐 필드만 담은 update, 바뀐 필드가 없으면 `None`
+/// `sent`가 없으면 모든 필드를 담는다.
+fn transform_update(entity: Entity, sent: Option<&Transform>, current: &Transform) -> Option<ServerMessage> {
+    let vec3_changed = |quantization: PositionQuantization, sent: Vec3, current: Vec3| quantization.quantize(sent) != quantization.quantize(current);
+
+    let translation = sent.is_none_or(|sent| vec3_changed(DEFAULT_POSITION_QUANTIZATION, sent.translation, current.translation)).then_some(current.translation);
+    let rotation = sent.is_none_or(|sent| quantize_rotation(sent.rotation) != quantize_rotation(current.rotation)).then_some(current.rotation);
+    let scale = sent.is_none_or(|sent| vec3_changed(DEFAULT_SCALE_QUANTIZATION, sent.scale, current.scale)).then_some(current.scale);
+
+    if translation.is_none() && rotation.is_none() && scale.is_none() {
+        return None;
+    }
+
+    Some(ServerMessage::PlayerUpdate { entity: entity.to_bits(), translation, rotation, scale })
+}
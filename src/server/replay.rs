@@ -0,0 +1,222 @@
+use std::{fs::File, io::{self, BufRead, BufReader, LineWriter, Write}};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::common::{ClientMessage, PlayerInput, TransportKind};
+
+use super::{auth::AccountId, deterministic::{world_hash, HeadlessServer, SimulationSeed}, movement::Velocity, outbound::{outbound_queue, OUTBOUND_QUEUE_CAPACITY}, Client, ClientConnectInfo, ClientEventMessage};
+
+/// 클라이언트 메시지 기록
+/// 처리한 모든 `ClientEventMessage`(접속, event, 접속 종료)를 처음 반영되는 고정 tick 번호와 함께 파일에 한 줄씩 남긴다.
+/// 첫 줄은 `SimulationSeed`이며, `cargo run replay <file>`로 소켓 없이 같은 월드를 다시 만든다. (`run_replay`)
+///
+/// 결정적 모드(`--seed`)로 기록하면 재생 결과가 비트 단위로 같다.
+/// 결정적 모드가 아니면 같은 tick에 접속한 클라이언트들의 스폰 위치가 달라질 수 있다. (재생은 클라이언트 id 순서로 처리)
+pub struct RecordPlugin {
+    pub path: String,
+}
+
+impl Plugin for RecordPlugin {
+    fn build(&self, app: &mut App) {
+        let path = self.path.clone();
+        app.add_systems(Startup, move |mut commands: Commands, seed: Res<SimulationSeed>| {
+            match MessageRecorder::create(&path, seed.0) {
+                Ok(recorder) => {
+                    println!("[record] recording client messages to {}, seed: {}", path, seed.0);
+                    commands.insert_resource(recorder);
+                },
+                Err(e) => eprintln!("[record] failed to create {}, err: {}", path, e),
+            }
+        });
+    }
+}
+
+// ----------------- record
+
+/// 기록 파일의 첫 줄
+#[derive(Serialize, Deserialize)]
+struct RecordingHeader {
+    seed: u64,
+}
+
+/// 기록 파일의 메시지 한 줄
+#[derive(Serialize, Deserialize, Clone)]
+struct RecordedMessage {
+    tick: u32,
+    client: String,
+    #[serde(flatten)]
+    kind: RecordedKind,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type")]
+enum RecordedKind {
+    Connect { account: String, transport: TransportKind },
+    Event { message: ClientMessage },
+    Disconnect,
+}
+
+impl RecordedMessage {
+    fn new(tick: u32, msg: &ClientEventMessage) -> Self {
+        let kind = match msg {
+            ClientEventMessage::Connect(info) => RecordedKind::Connect { account: info.account.0.clone(), transport: info.transport },
+            ClientEventMessage::Event(client_msg, _) => RecordedKind::Event { message: client_msg.clone() },
+            ClientEventMessage::Disconnect(_) => RecordedKind::Disconnect,
+        };
+        RecordedMessage { tick, client: msg.uuid().to_string(), kind }
+    }
+
+    /// 재생할 때는 보낼 곳이 없으므로 송신 대기열은 받는 쪽 없이 만든다.
+    fn into_message(self) -> Result<ClientEventMessage, uuid::Error> {
+        let uuid = Uuid::parse_str(&self.client)?;
+        Ok(match self.kind {
+            RecordedKind::Connect { account, transport } => {
+                let (sender, _) = outbound_queue(OUTBOUND_QUEUE_CAPACITY);
+                ClientEventMessage::Connect(ClientConnectInfo::new(uuid, AccountId(account), sender, transport))
+            },
+            RecordedKind::Event { message } => ClientEventMessage::Event(message, uuid),
+            RecordedKind::Disconnect => ClientEventMessage::Disconnect(uuid),
+        })
+    }
+}
+
+// ----------------- resource
+
+/// 기록 파일, 서버가 비정상 종료돼도 남도록 줄마다 flush한다.
+#[derive(Resource)]
+pub struct MessageRecorder(LineWriter<File>);
+
+impl MessageRecorder {
+    fn create(path: &str, seed: u64) -> io::Result<Self> {
+        let mut writer = LineWriter::new(File::create(path)?);
+        writeln!(writer, "{}", serde_json::to_string(&RecordingHeader { seed })?)?;
+        Ok(MessageRecorder(writer))
+    }
+
+    /// `tick`: 이 메시지가 처음 반영되는 고정 tick
+    pub fn record(&mut self, tick: u32, msg: &ClientEventMessage) {
+        let line = serde_json::to_string(&RecordedMessage::new(tick, msg)).unwrap();
+        if let Err(e) = writeln!(self.0, "{}", line) {
+            eprintln!("[record] failed to write, err: {}", e);
+        }
+    }
+}
+
+// ----------------- replay
+
+/// 기록 파일을 읽어서 소켓 없이 한 tick씩 다시 진행시킨다.
+/// `until`이 있으면 그 tick까지 진행해서 상태를 출력하고 끝내고, 없으면 표준 입력으로 명령을 받는다.
+pub fn run_replay(path: &str, until: Option<u32>) {
+    let (seed, messages) = match load_recording(path) {
+        Ok(recording) => recording,
+        Err(e) => {
+            eprintln!("[replay] failed to load {}, err: {}", path, e);
+            return;
+        },
+    };
+    let last_tick = messages.last().map_or(0, |msg| msg.tick);
+    println!("[replay] {}, seed: {}, messages: {}, last tick: {}", path, seed, messages.len(), last_tick);
+
+    let mut replay = Replay::new(seed, messages);
+    if let Some(until) = until {
+        replay.go_to(until);
+        replay.print_state();
+        return;
+    }
+
+    println!("[replay] commands: <enter> | s [n]: n tick 진행, g <tick>: tick으로 이동, p: 상태 출력, q: 종료");
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        let mut args = line.split_whitespace();
+        match (args.next(), args.next().and_then(|arg| arg.parse::<u32>().ok())) {
+            (None, _) => replay.step(1),
+            (Some("s"), count) => replay.step(count.unwrap_or(1)),
+            (Some("g"), Some(tick)) => replay.go_to(tick),
+            (Some("p"), _) => {},
+            (Some("q"), _) => break,
+            _ => {
+                println!("[replay] unknown command: {}", line);
+                continue;
+            },
+        }
+        replay.print_state();
+    }
+}
+
+fn load_recording(path: &str) -> Result<(u64, Vec<RecordedMessage>), String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let mut lines = BufReader::new(file).lines();
+
+    let header = lines.next().ok_or("empty recording")?.map_err(|e| e.to_string())?;
+    let header: RecordingHeader = serde_json::from_str(&header).map_err(|e| format!("invalid header: {}", e))?;
+
+    let mut messages = Vec::new();
+    for (index, line) in lines.enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        let msg: RecordedMessage = serde_json::from_str(&line).map_err(|e| format!("invalid message at line {}: {}", index + 2, e))?;
+        messages.push(msg);
+    }
+    // 같은 tick 안에서는 기록된 순서를 유지한다.
+    messages.sort_by_key(|msg| msg.tick);
+
+    Ok((header.seed, messages))
+}
+
+/// 시뮬레이션은 앞으로만 진행되므로 이전 tick으로 돌아갈 때는 처음부터 다시 진행한다.
+struct Replay {
+    seed: u64,
+    messages: Vec<RecordedMessage>,
+    /// 다음에 넣을 메시지 위치
+    cursor: usize,
+    server: HeadlessServer,
+}
+
+impl Replay {
+    fn new(seed: u64, messages: Vec<RecordedMessage>) -> Self {
+        Replay { seed, messages, cursor: 0, server: HeadlessServer::new(seed) }
+    }
+
+    fn step(&mut self, count: u32) {
+        for _ in 0..count {
+            let next_tick = self.server.tick() + 1;
+            let mut batch = Vec::new();
+            while let Some(recorded) = self.messages.get(self.cursor).filter(|recorded| recorded.tick <= next_tick) {
+                match recorded.clone().into_message() {
+                    Ok(msg) => batch.push(msg),
+                    Err(e) => eprintln!("[replay] invalid client id, err: {}", e),
+                }
+                self.cursor += 1;
+            }
+            self.server.step(batch);
+        }
+    }
+
+    fn go_to(&mut self, tick: u32) {
+        if tick < self.server.tick() {
+            self.server = HeadlessServer::new(self.seed);
+            self.cursor = 0;
+        }
+        let count = tick - self.server.tick();
+        self.step(count);
+    }
+
+    /// 클라이언트 id 순서로 플레이어 상태를 출력한다.
+    fn print_state(&mut self) {
+        let tick = self.server.tick();
+        let world = self.server.world_mut();
+        let mut players: Vec<(Uuid, String, Vec3, Vec2, PlayerInput)> = world.query::<(&Client, &AccountId, &Transform, &Velocity, &PlayerInput)>()
+            .iter(world)
+            .map(|(client, account, transform, velocity, input)| (client.0, account.0.clone(), transform.translation, velocity.0, *input))
+            .collect();
+        players.sort_by_key(|player| player.0);
+
+        println!("[replay] tick: {}, players: {}, hash: {:016x}", tick, players.len(), world_hash(world));
+        for (uuid, account, translation, velocity, input) in players {
+            println!("  {} ({}) position: ({:.2}, {:.2}), velocity: ({:.2}, {:.2}), buttons: {:04b}", uuid, account, translation.x, translation.y, velocity.x, velocity.y, input.buttons);
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use bevy::prelude::*;
+use serde::Serialize;
+
+use crate::common::{NetworkMessage, ServerMessage};
+
+use super::{aggregate::OutboundBuffer, ReplicationSet};
+
+/// 서버 `Resource` 복제
+/// `app.replicate_resource::<R>()`로 등록하면 새로 접속한 클라이언트에게 현재 값을 보내고,
+/// 이후 값이 바뀔 때마다 모든 클라이언트에게 다시 보낸다.
+/// 클라이언트도 같은 타입을 `replicate_resource`로 등록해야 받을 수 있으며, 이름은 `NetworkMessage::NAME`으로 구분한다.
+pub trait ReplicateResourceExt {
+    fn replicate_resource<R: Resource + Serialize + NetworkMessage>(&mut self) -> &mut Self;
+}
+
+impl ReplicateResourceExt for App {
+    fn replicate_resource<R: Resource + Serialize + NetworkMessage>(&mut self) -> &mut Self {
+        self.add_systems(PostUpdate, replicate_resource_system::<R>.in_set(ReplicationSet::Send))
+    }
+}
+
+// ----------------- system
+
+/// `OutboundBuffer`는 접속할 때 추가되므로 `is_added`로 새로 접속한 클라이언트를 찾는다.
+fn replicate_resource_system<R: Resource + Serialize + NetworkMessage>(resource: Option<Res<R>>, mut clients: Query<&mut OutboundBuffer>) {
+    let Some(resource) = resource else {
+        return;
+    };
+
+    let changed = resource.is_changed();
+    let mut value = None;
+    for mut buffer in clients.iter_mut() {
+        if !changed && !buffer.is_added() {
+            continue;
+        }
+
+        if value.is_none() {
+            match serde_json::to_value(&*resource) {
+                Ok(serialized) => value = Some(serialized),
+                Err(e) => {
+                    eprintln!("[replicate_resource] failed to serialize {}: {}", R::NAME, e);
+                    return;
+                },
+            }
+        }
+        buffer.push(ServerMessage::ResourceUpdate { name: R::NAME.to_string(), value: value.clone().unwrap() });
+    }
+}
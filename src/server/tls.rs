@@ -0,0 +1,29 @@
+use std::sync::Arc;
+
+use tokio_rustls::{rustls::{self, pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer}}, TlsAcceptor};
+
+/// 서버 TLS(wss://) 설정
+/// 인증서와 개인키는 PEM 파일 경로로 지정한다.
+pub struct TlsConfig {
+    pub cert_path: String,
+    pub key_path: String,
+}
+
+impl TlsConfig {
+    /// 인증서 체인과 개인키를 읽어서 WebSocket listener에서 사용할 `TlsAcceptor`를 만든다.
+    pub fn load_acceptor(&self) -> Result<TlsAcceptor, String> {
+        let certs = CertificateDer::pem_file_iter(&self.cert_path)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| format!("fail to read certificate {}, error: {}", self.cert_path, e))?;
+        let key = PrivateKeyDer::from_pem_file(&self.key_path)
+            .map_err(|e| format!("fail to read private key {}, error: {}", self.key_path, e))?;
+
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let config = rustls::ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key))
+            .map_err(|e| format!("invalid tls configuration, error: {}", e))?;
+
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+}
@@ -0,0 +1,223 @@
+use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::{Duration, Instant}};
+
+use tokio::{net::UdpSocket, sync::mpsc::{Receiver, Sender}};
+use uuid::Uuid;
+
+use crate::common::{udp::{UdpConnection, UdpPacket, MAX_PAYLOAD}, ChannelKind, ClientMessage, TransportKind};
+
+use super::{admission::{Admission, Violation}, auth::{AccountId, Authenticator}, outbound::{outbound_queue, OutboundReceiver, OUTBOUND_QUEUE_CAPACITY}, encode_packet, ClientConnectInfo, ClientEventMessage};
+
+/// challenge를 보내고 응답을 기다리는 주소가 너무 많아지면 비운다.
+const MAX_PENDING_CHALLENGES: usize = 1024;
+
+/// UDP 서버
+/// `handle_websocket`과 같은 역할로, 하나의 소켓으로 모든 클라이언트의 datagram을 받아서
+/// handshake가 끝난 주소는 연결마다 생성한 task(`handle_udp_connection`)로 넘겨준다.
+/// challenge 응답에 담긴 토큰으로 인증에 실패하거나 접속 제한을 넘으면 `Denied`를 보내고 연결을 만들지 않는다.
+pub async fn handle_udp(tx: Sender<ClientEventMessage>, authenticator: Arc<dyn Authenticator>, admission: Admission) {
+    let socket = Arc::new(UdpSocket::bind("0.0.0.0:9004").await.unwrap());
+
+    let mut connections: HashMap<SocketAddr, Sender<UdpPacket>> = HashMap::new();
+    let mut challenges: HashMap<SocketAddr, (u64, u64)> = HashMap::new();
+    let (closed_tx, mut closed_rx) = tokio::sync::mpsc::channel::<SocketAddr>(16);
+
+    let mut buf = vec![0u8; 2048];
+
+    loop {
+        tokio::select! {
+            result = socket.recv_from(&mut buf) => {
+                let (len, addr) = match result {
+                    Ok(result) => result,
+                    Err(e) => {
+                        eprintln!("[udp] recv error: {}", e);
+                        continue;
+                    },
+                };
+                let Some(packet) = UdpPacket::decode(&buf[..len]) else {
+                    continue;
+                };
+
+                if let Some(packet_tx) = connections.get(&addr) {
+                    match packet {
+                        // Accepted가 유실된 경우 다시 보내준다.
+                        UdpPacket::ChallengeResponse { .. } => send_to(&socket, &UdpPacket::Accepted.encode(), addr).await,
+                        packet => {
+                            let _ = packet_tx.try_send(packet);
+                        },
+                    }
+                    continue;
+                }
+
+                match packet {
+                    UdpPacket::ConnectRequest { client_salt } => {
+                        if challenges.len() >= MAX_PENDING_CHALLENGES {
+                            challenges.clear();
+                        }
+                        let server_salt = Uuid::new_v4().as_u64_pair().0;
+                        challenges.insert(addr, (client_salt, server_salt));
+                        send_to(&socket, &UdpPacket::Challenge { client_salt, server_salt }.encode(), addr).await;
+                    },
+                    UdpPacket::ChallengeResponse { client_salt, server_salt, token } => {
+                        if challenges.get(&addr) != Some(&(client_salt, server_salt)) {
+                            continue;
+                        }
+                        challenges.remove(&addr);
+
+                        let token = Some(token.as_str()).filter(|token| !token.is_empty());
+                        let account = match authenticator.authenticate(token) {
+                            Ok(account) => AccountId(account),
+                            Err(reason) => {
+                                println!("[udp] client rejected, addr: {}, reason: {}", addr, reason);
+                                send_to(&socket, &UdpPacket::Denied { reason }.encode(), addr).await;
+                                continue;
+                            },
+                        };
+
+                        let permits = admission.admit_connection(addr.ip())
+                            .and_then(|connection_permit| Ok((connection_permit, admission.admit_player()?)));
+                        let permits = match permits {
+                            Ok(permits) => permits,
+                            Err(reason) => {
+                                println!("[udp] client rejected, addr: {}, reason: {}", addr, reason);
+                                send_to(&socket, &UdpPacket::Denied { reason }.encode(), addr).await;
+                                continue;
+                            },
+                        };
+
+                        let uuid = Uuid::new_v4();
+                        println!("[udp] client connected, addr: {}, uuid: {}, account: {}", addr, uuid, account.0);
+
+                        let (packet_tx, packet_rx) = tokio::sync::mpsc::channel::<UdpPacket>(256);
+                        let (sink_tx, sink_rx) = outbound_queue(OUTBOUND_QUEUE_CAPACITY);
+
+                        if let Err(e) = tx.send(ClientEventMessage::Connect(ClientConnectInfo::new(uuid, account, sink_tx, TransportKind::Udp))).await {
+                            eprintln!("fail to send message that requests to make client entity, error: {}", e);
+                            continue;
+                        }
+
+                        connections.insert(addr, packet_tx);
+                        send_to(&socket, &UdpPacket::Accepted.encode(), addr).await;
+
+                        let socket = socket.clone();
+                        let tx = tx.clone();
+                        let admission = admission.clone();
+                        let closed_tx = closed_tx.clone();
+                        tokio::spawn(async move {
+                            // 연결이 끝날 때까지 접속 수에 포함된다.
+                            let _permits = permits;
+                            handle_udp_connection(socket, addr, uuid, packet_rx, sink_rx, tx, admission).await;
+                            let _ = closed_tx.send(addr).await;
+                        });
+                    },
+                    _ => {},
+                }
+            },
+            Some(addr) = closed_rx.recv() => {
+                connections.remove(&addr);
+            },
+        }
+    }
+}
+
+/// 연결된 각 UDP 클라이언트마다 task로 존재함. (`handle_accept` + `sink_handler` 역할)
+/// 받은 packet은 `UdpConnection`으로 ack / 순서를 처리하여 bevy로 보내고,
+/// bevy에서 보낸 메시지는 datagram으로 만들어 보낸다.
+/// WebSocket과 같은 수신 제한(메시지 크기, token bucket)이나 datagram 수 제한을 넘으면 연결을 끊는다.
+async fn handle_udp_connection(
+    socket: Arc<UdpSocket>,
+    addr: SocketAddr,
+    uuid: Uuid,
+    mut packets: Receiver<UdpPacket>,
+    mut sink_recv: OutboundReceiver,
+    tx: Sender<ClientEventMessage>,
+    admission: Admission,
+) {
+    let mut connection = UdpConnection::new(Instant::now());
+    let mut rate_limiter = admission.rate_limiter();
+    let mut datagram_limiter = admission.datagram_rate_limiter();
+    let mut interval = tokio::time::interval(Duration::from_millis(10));
+
+    'connection: loop {
+        tokio::select! {
+            Some(packet) = packets.recv() => {
+                if let UdpPacket::Disconnect = packet {
+                    break;
+                }
+                if !datagram_limiter.allow(Instant::now()) {
+                    let count = admission.record_violation(Violation::RateLimited);
+                    println!("[udp] disconnect client, addr: {}, reason: too many datagrams (rate limited total: {})", addr, count);
+                    break;
+                }
+
+                for payload in connection.receive(packet, Instant::now()) {
+                    if payload.len() > admission.max_message_size() {
+                        let count = admission.record_violation(Violation::Oversized);
+                        println!("[udp] disconnect client, addr: {}, reason: message too large, size: {} (oversized total: {})", addr, payload.len(), count);
+                        break 'connection;
+                    }
+                    if !rate_limiter.allow(Instant::now()) {
+                        let count = admission.record_violation(Violation::RateLimited);
+                        println!("[udp] disconnect client, addr: {}, reason: too many messages (rate limited total: {})", addr, count);
+                        break 'connection;
+                    }
+
+                    let client_msg = match serde_json::from_slice::<ClientMessage>(&payload) {
+                        Ok(client_msg) => client_msg,
+                        Err(e) => {
+                            eprintln!("[udp] invalid message, addr: {}, error: {}", addr, e);
+                            continue;
+                        },
+                    };
+                    if let Err(e) = tx.send(ClientEventMessage::Event(client_msg, uuid)).await {
+                        eprintln!("ClientEventMessage send error: {}", e);
+                    }
+                }
+            },
+            outgoing = sink_recv.recv() => {
+                let Some(pending) = outgoing else {
+                    if sink_recv.overflowed() {
+                        println!("[udp] disconnect client, addr: {}, reason: send queue overflow", addr);
+                    }
+                    break;
+                };
+
+                for outgoing in pending {
+                    let message = encode_packet(&outgoing);
+                    // 순서 번호가 이미 할당됐으므로 reliable 메시지를 버리면 이후 메시지가 전달되지 않는다.
+                    if message.len() > MAX_PAYLOAD {
+                        if outgoing.channel == ChannelKind::UnreliableSequenced {
+                            eprintln!("[udp] message is too large to send, size: {}", message.len());
+                            continue;
+                        }
+                        println!("[udp] disconnect client, addr: {}, reason: reliable message too large to send, size: {}", addr, message.len());
+                        break 'connection;
+                    }
+                    let datagram = connection.send(outgoing.channel, outgoing.seq, message.into_bytes(), Instant::now());
+                    send_to(&socket, &datagram, addr).await;
+                }
+            },
+            _ = interval.tick() => {
+                let now = Instant::now();
+                if connection.is_timed_out(now) {
+                    println!("[udp] connection timed out, addr: {}", addr);
+                    break;
+                }
+                for datagram in connection.poll(now) {
+                    send_to(&socket, &datagram, addr).await;
+                }
+            },
+        }
+    }
+
+    send_to(&socket, &UdpPacket::Disconnect.encode(), addr).await;
+
+    if let Err(e) = tx.send(ClientEventMessage::Disconnect(uuid)).await {
+        eprintln!("ClientEventMessage send error: {}", e);
+    }
+}
+
+async fn send_to(socket: &UdpSocket, datagram: &[u8], addr: SocketAddr) {
+    if let Err(e) = socket.send_to(datagram, addr).await {
+        eprintln!("[udp] send error: {}", e);
+    }
+}